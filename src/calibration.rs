@@ -0,0 +1,101 @@
+use std::collections::HashMap;
+use std::path::Path;
+
+/// What the calibration file says about a pixel.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum CalibrationStatus {
+    // Has a valid time offset, the offsets are not applied to any times here
+    Calibrated,
+    // Excluded from the calibration, e.g. a noisy pixel
+    Masked,
+    // Not in the calibration file
+    Missing,
+}
+
+/// Per pixel time calibration, read from a csv of `x,y,time_offset_ps`
+/// lines. An offset of `masked` or an empty one masks the pixel.
+#[derive(Clone, Debug, Default)]
+pub struct Calibration {
+    pixels: HashMap<(u8, u8), CalibrationStatus>,
+}
+
+impl Calibration {
+    pub fn load(file: &Path) -> Result<Self, String> {
+        let content = std::fs::read_to_string(file)
+            .map_err(|e| format!("Could not read {}: {}", file.display(), e))?;
+        content
+            .parse()
+            .map_err(|e| format!("{}:{}", file.display(), e))
+    }
+
+    pub fn status(&self, pixel: (u8, u8)) -> CalibrationStatus {
+        self.pixels
+            .get(&pixel)
+            .copied()
+            .unwrap_or(CalibrationStatus::Missing)
+    }
+
+    pub fn len(&self) -> usize {
+        self.pixels.len()
+    }
+}
+
+// Errors start with the line number, `load` puts the file name in front
+impl std::str::FromStr for Calibration {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut pixels = HashMap::new();
+        for (line_nr, line) in s.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let err = |e: String| format!("{}: {}", line_nr + 1, e);
+            let cols: Vec<&str> = line.split(',').map(|c| c.trim()).collect();
+            if cols.len() != 3 {
+                return Err(err(format!("expected 3 columns, got {}", cols.len())));
+            }
+            let parse_coord = |col: &str| {
+                col.parse::<u8>()
+                    .map_err(|e| err(format!("'{}': {}", col, e)))
+            };
+            let pixel = (parse_coord(cols[0])?, parse_coord(cols[1])?);
+            let status = match cols[2] {
+                "" | "masked" => CalibrationStatus::Masked,
+                offset => {
+                    offset
+                        .parse::<f64>()
+                        .map_err(|e| err(format!("'{}': {}", offset, e)))?;
+                    CalibrationStatus::Calibrated
+                }
+            };
+            pixels.insert(pixel, status);
+        }
+        Ok(Calibration { pixels })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_offsets_and_masked_pixels() {
+        let calibration: Calibration = "# x,y,time_offset_ps\n0,1,12.5\n2,3,masked\n4,5,\n"
+            .parse()
+            .unwrap();
+        assert_eq!(calibration.len(), 3);
+        assert_eq!(calibration.status((0, 1)), CalibrationStatus::Calibrated);
+        assert_eq!(calibration.status((2, 3)), CalibrationStatus::Masked);
+        assert_eq!(calibration.status((4, 5)), CalibrationStatus::Masked);
+        assert_eq!(calibration.status((9, 9)), CalibrationStatus::Missing);
+    }
+
+    #[test]
+    fn errors_name_the_line() {
+        let err = "0,1,2\n0,x,2\n".parse::<Calibration>().unwrap_err();
+        assert!(err.starts_with("2: 'x'"), "{}", err);
+        assert!("0,1\n".parse::<Calibration>().is_err());
+    }
+}
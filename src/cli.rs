@@ -0,0 +1,462 @@
+use clap::{Args, Parser, Subcommand};
+use std::path::PathBuf;
+
+use std::io::Write;
+
+use crate::calibration::Calibration;
+use crate::colors::ColorMode;
+use crate::export::{export_hits, HitSelection, HitsFormat};
+use crate::generator::{generate, parse_pixel, BeamProfile, GeneratorConfig, TestPulse};
+use crate::hitmap::HitCounts;
+use crate::net::{write_chunks, Protocol, WireFormat};
+use crate::render::{
+    draw_histogram, draw_hit_counts, hit_counts_height, render_chunk, render_titled, RenderSettings,
+};
+use crate::replay::{replay, Pace, ReplayConfig};
+use crate::report::{build_report, ReportConfig};
+use crate::simulation::{parse_true_hits_csv, simulate_frame, HitFate, SimulationConfig};
+use crate::stats::{start_time_histogram, tot_histogram};
+use crate::tdcpix::{parse_tdcpix_txt, write_tdcpix_txt, FRAME_PERIOD_PS};
+use crate::widgets::timeline::{format_time, LaneGrouping};
+
+// A frame word is written for every frame in between the first and last hit,
+// so a stray frame number in the input could otherwise produce a huge file
+const MAX_SIMULATED_FRAMES: u32 = 1_000_000;
+
+/// Without a command the visualizer window is opened.
+#[derive(Parser)]
+#[command(version, about = "TDCpix data visualizer")]
+pub struct Cli {
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Subcommand)]
+pub enum Command {
+    /// Generate synthetic TDCpix data
+    Generate(GenerateArgs),
+    /// Simulate the chip output for a csv of true hits (frame,x,y,time_ps,charge_fc)
+    Simulate(SimulateArgs),
+    /// Stream a capture file to a receiver over TCP or UDP
+    Replay(ReplayArgs),
+    /// Render the hitmap, a chunk and histograms of a capture file to png
+    Render(RenderArgs),
+    /// Write a self-contained html report with statistics of a capture file
+    Report(ReportArgs),
+    /// Export the decoded data words of a capture file to csv or json lines
+    Hits(HitsArgs),
+}
+
+#[derive(Args)]
+pub struct GenerateArgs {
+    /// Output file
+    #[arg(short, long)]
+    output: PathBuf,
+    /// Hex text, or binary words as sent over the network
+    #[arg(long, value_enum, default_value_t = WireFormat::Hex)]
+    format: WireFormat,
+    #[arg(long, default_value_t = 0)]
+    seed: u64,
+    #[arg(long, default_value_t = 10)]
+    chunks: usize,
+    #[arg(long, default_value_t = 0)]
+    first_frame: u32,
+    /// Mean number of beam hits per chunk
+    #[arg(long, default_value_t = 3.5)]
+    hits: f32,
+    /// "uniform" or "gaussian:x,y,sigma_x,sigma_y"
+    #[arg(long, default_value = "uniform")]
+    beam: BeamProfile,
+    /// Mean number of noise hits per chunk
+    #[arg(long, default_value_t = 0.0)]
+    noise: f32,
+    /// Chance that a hit flags pileup
+    #[arg(long, default_value_t = 1.0 / 333.0)]
+    pileup: f32,
+    /// Dead pixel as x,y, can be given multiple times
+    #[arg(long = "dead", value_parser = parse_pixel)]
+    dead_pixels: Vec<(u8, u8)>,
+    /// "off", "column:N", "row:N", "diagonal" or "checkerboard"
+    #[arg(long, default_value = "off")]
+    test_pulse: TestPulse,
+    /// Inject the test pulse every n chunks
+    #[arg(long, default_value_t = 1, value_parser = clap::value_parser!(u64).range(1..))]
+    test_pulse_period: u64,
+    #[arg(long, default_value_t = 100_000.0)]
+    tot_mean_ps: f32,
+    #[arg(long, default_value_t = 50_000.0)]
+    tot_sigma_ps: f32,
+}
+
+#[derive(Args)]
+pub struct SimulateArgs {
+    /// Csv file with the true hits
+    input: PathBuf,
+    /// Output file
+    #[arg(short, long)]
+    output: PathBuf,
+    /// Csv file telling which word each true hit ended up in
+    #[arg(long)]
+    truth_output: Option<PathBuf>,
+    #[arg(long, default_value_t = 0.7)]
+    threshold_fc: f32,
+    #[arg(long, default_value_t = 10_000.0)]
+    tot_ps_per_fc: f32,
+    #[arg(long, default_value_t = 3_125)]
+    simultaneity_ps: u64,
+}
+
+#[derive(Args)]
+pub struct ReplayArgs {
+    /// Capture file to send
+    input: PathBuf,
+    /// host:port of the receiver
+    #[arg(short, long, default_value = "127.0.0.1:5000")]
+    address: String,
+    #[arg(long, value_enum, default_value_t = Protocol::Tcp)]
+    protocol: Protocol,
+    #[arg(long, value_enum, default_value_t = WireFormat::Hex)]
+    format: WireFormat,
+    /// Send at a fixed number of words per second instead of real time
+    #[arg(long, conflicts_with = "fast")]
+    rate: Option<f64>,
+    /// Send as fast as possible instead of real time
+    #[arg(long)]
+    fast: bool,
+    /// Start over when the end of the file is reached
+    #[arg(long = "loop")]
+    repeat: bool,
+}
+
+#[derive(Args)]
+pub struct RenderArgs {
+    /// Capture file to render
+    input: PathBuf,
+    /// Directory the images are written to, created if missing
+    #[arg(short, long)]
+    output_dir: PathBuf,
+    /// Chunk shown with its timeline
+    #[arg(long, default_value_t = 0)]
+    chunk: usize,
+    /// Number of chunks on the timeline
+    #[arg(long, default_value_t = 1, value_parser = clap::value_parser!(u64).range(1..))]
+    timeline_chunks: u64,
+    #[arg(long, value_enum, default_value_t = ColorMode::Pileup)]
+    color_mode: ColorMode,
+    #[arg(long, value_enum, default_value_t = LaneGrouping::Address)]
+    grouping: LaneGrouping,
+    /// Csv of x,y,time_offset_ps lines for the calibration color mode
+    #[arg(long)]
+    calibration: Option<PathBuf>,
+    /// Image width in pixels
+    #[arg(long, default_value_t = 800)]
+    width: u32,
+    /// Number of bins of the histograms
+    #[arg(long, default_value_t = 128)]
+    bins: usize,
+}
+
+#[derive(Args)]
+pub struct ReportArgs {
+    /// Capture file to report on
+    input: PathBuf,
+    /// Output html file
+    #[arg(short, long)]
+    output: PathBuf,
+    /// Pixels hit more than this many times the average are listed as noisy
+    #[arg(long, default_value_t = 5.0)]
+    noisy_factor: f64,
+    /// Number of bins of the histograms
+    #[arg(long, default_value_t = 128)]
+    bins: usize,
+    /// Issues listed one by one, the rest are only counted
+    #[arg(long, default_value_t = 200)]
+    max_issues: usize,
+}
+
+#[derive(Args)]
+pub struct HitsArgs {
+    /// Capture file to decode
+    input: PathBuf,
+    /// Output file
+    #[arg(short, long)]
+    output: PathBuf,
+    #[arg(long, value_enum, default_value_t = HitsFormat::Csv)]
+    format: HitsFormat,
+    /// First chunk exported
+    #[arg(long)]
+    first: Option<usize>,
+    /// Last chunk exported
+    #[arg(long)]
+    last: Option<usize>,
+    /// Only export the words of pixel x,y
+    #[arg(long, value_parser = parse_pixel)]
+    pixel: Option<(u8, u8)>,
+}
+
+pub fn run(command: Command) -> Result<(), String> {
+    match command {
+        Command::Generate(args) => run_generate(args),
+        Command::Simulate(args) => run_simulate(args),
+        Command::Replay(args) => run_replay(args),
+        Command::Render(args) => run_render(args),
+        Command::Report(args) => run_report(args),
+        Command::Hits(args) => run_hits(args),
+    }
+}
+
+fn run_generate(args: GenerateArgs) -> Result<(), String> {
+    let config = GeneratorConfig {
+        seed: args.seed,
+        num_chunks: args.chunks,
+        first_frame: args.first_frame,
+        hits_per_chunk: args.hits,
+        beam: args.beam,
+        noise_per_chunk: args.noise,
+        pileup_probability: args.pileup,
+        dead_pixels: args.dead_pixels,
+        test_pulse: args.test_pulse,
+        test_pulse_period: args.test_pulse_period as usize,
+        tot_mean_ps: args.tot_mean_ps,
+        tot_sigma_ps: args.tot_sigma_ps,
+    };
+    let chunks = generate(&config);
+    write_chunks(&args.output, &chunks, args.format)
+        .map_err(|e| format!("Could not write {}: {}", args.output.display(), e))
+}
+
+fn run_simulate(args: SimulateArgs) -> Result<(), String> {
+    let config = SimulationConfig {
+        threshold_fc: args.threshold_fc,
+        tot_ps_per_fc: args.tot_ps_per_fc,
+        simultaneity_ps: args.simultaneity_ps,
+    };
+    let frames = parse_true_hits_csv(&args.input)?;
+    let (first, last) = match (frames.keys().next(), frames.keys().next_back()) {
+        (Some(&first), Some(&last)) => (first, last),
+        _ => return Err(format!("{} contains no hits", args.input.display())),
+    };
+    if first > last || last - first >= MAX_SIMULATED_FRAMES {
+        return Err(format!(
+            "{} spans frames {} to {}, at most {} frames can be simulated",
+            args.input.display(),
+            first,
+            last,
+            MAX_SIMULATED_FRAMES
+        ));
+    }
+
+    let mut truth = String::from("frame,x,y,time_ps,charge_fc,chunk_idx,fate,word_idx\n");
+    let mut chunks = Vec::new();
+    let mut out_of_frame = 0;
+    // The chip sends a frame word every frame, also the ones without hits
+    for frame_counter in first..=last {
+        let hits = frames.get(&frame_counter).map_or(&[][..], |h| &h[..]);
+        let simulated = simulate_frame(hits, frame_counter, &config);
+        for (hit, fate) in hits.iter().zip(simulated.fates) {
+            let (fate, word_idx) = match fate {
+                HitFate::Word(i) => ("word", i.to_string()),
+                HitFate::Pileup(i) => ("pileup", i.to_string()),
+                HitFate::BelowThreshold => ("below_threshold", String::new()),
+                HitFate::Unmapped => ("unmapped", String::new()),
+                HitFate::OutOfFrame => {
+                    out_of_frame += 1;
+                    ("out_of_frame", String::new())
+                }
+            };
+            truth.push_str(&format!(
+                "{},{},{},{},{},{},{},{}\n",
+                frame_counter,
+                hit.x,
+                hit.y,
+                hit.time,
+                hit.charge,
+                chunks.len(),
+                fate,
+                word_idx
+            ));
+        }
+        chunks.push(simulated.chunk);
+    }
+    if out_of_frame > 0 {
+        eprintln!(
+            "{} hits were left out, their time is not within the frame of {} ps",
+            out_of_frame, FRAME_PERIOD_PS
+        );
+    }
+
+    write_tdcpix_txt(&args.output, &chunks)
+        .map_err(|e| format!("Could not write {}: {}", args.output.display(), e))?;
+    if let Some(truth_output) = args.truth_output {
+        std::fs::File::create(&truth_output)
+            .and_then(|mut f| f.write_all(truth.as_bytes()))
+            .map_err(|e| format!("Could not write {}: {}", truth_output.display(), e))?;
+    }
+    Ok(())
+}
+
+fn run_replay(args: ReplayArgs) -> Result<(), String> {
+    let mut chunks = Vec::new();
+    parse_tdcpix_txt(&args.input, &mut chunks)?;
+
+    let pace = match (args.rate, args.fast) {
+        (_, true) => Pace::AsFastAsPossible,
+        (Some(rate), _) if rate > 0.0 => Pace::WordsPerSecond(rate),
+        (Some(rate), _) => return Err(format!("Rate must be positive, got {}", rate)),
+        (None, false) => Pace::RealTime,
+    };
+    let config = ReplayConfig {
+        protocol: args.protocol,
+        format: args.format,
+        address: args.address,
+        pace,
+        repeat: args.repeat,
+    };
+
+    let start = std::time::Instant::now();
+    let words = replay(&chunks, &config)
+        .map_err(|e| format!("Could not send to {}: {}", config.address, e))?;
+    println!(
+        "Sent {} words in {:.3} s",
+        words,
+        start.elapsed().as_secs_f64()
+    );
+    Ok(())
+}
+
+fn run_render(args: RenderArgs) -> Result<(), String> {
+    let mut chunks = Vec::new();
+    parse_tdcpix_txt(&args.input, &mut chunks)?;
+    if args.chunk >= chunks.len() {
+        return Err(format!(
+            "{} has {} chunks, there is no chunk {}",
+            args.input.display(),
+            chunks.len(),
+            args.chunk
+        ));
+    }
+    std::fs::create_dir_all(&args.output_dir)
+        .map_err(|e| format!("Could not create {}: {}", args.output_dir.display(), e))?;
+    let file_name = args
+        .input
+        .file_name()
+        .map(|n| n.to_string_lossy().into_owned())
+        .unwrap_or_default();
+
+    let settings = RenderSettings {
+        width: args.width,
+        include_timeline: true,
+        color_mode: args.color_mode,
+        grouping: args.grouping,
+        timeline_chunks: args.timeline_chunks as usize,
+        timeline_view: None,
+        file_name: Some(file_name.clone()),
+        calibration: match &args.calibration {
+            Some(path) => Calibration::load(path)?,
+            None => Calibration::default(),
+        },
+        first_chunk_idx: 0,
+    };
+    let counts = HitCounts::from_chunks(&chunks);
+    let histogram_height = args.width / 2;
+
+    let images = [
+        (
+            "hitmap.png".to_string(),
+            render_titled(
+                args.width,
+                hit_counts_height(args.width),
+                &format!(
+                    "{}   {} chunks   {} words",
+                    file_name,
+                    chunks.len(),
+                    counts.total()
+                ),
+                |canvas, rect| draw_hit_counts(canvas, rect, &counts),
+            ),
+        ),
+        (
+            format!("chunk_{}.png", args.chunk),
+            render_chunk(&settings, &chunks, args.chunk, None),
+        ),
+        (
+            "start_time.png".to_string(),
+            render_titled(
+                args.width,
+                histogram_height,
+                &format!("{}   leading time", file_name),
+                |canvas, rect| {
+                    draw_histogram(
+                        canvas,
+                        rect,
+                        &start_time_histogram(&chunks, args.bins),
+                        format_time,
+                    )
+                },
+            ),
+        ),
+        (
+            "tot.png".to_string(),
+            render_titled(
+                args.width,
+                histogram_height,
+                &format!("{}   time over threshold", file_name),
+                |canvas, rect| {
+                    draw_histogram(
+                        canvas,
+                        rect,
+                        &tot_histogram(&chunks, args.bins),
+                        format_time,
+                    )
+                },
+            ),
+        ),
+    ];
+
+    for (name, image) in images {
+        let path = args.output_dir.join(name);
+        image
+            .save(&path)
+            .map_err(|e| format!("Could not write {}: {}", path.display(), e))?;
+        println!("Wrote {}", path.display());
+    }
+    Ok(())
+}
+
+fn run_report(args: ReportArgs) -> Result<(), String> {
+    let mut chunks = Vec::new();
+    parse_tdcpix_txt(&args.input, &mut chunks)?;
+    let config = ReportConfig {
+        noisy_factor: args.noisy_factor,
+        bins: args.bins,
+        width: 800,
+        max_listed_issues: args.max_issues,
+    };
+    let name = args
+        .input
+        .file_name()
+        .map(|n| n.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    std::fs::write(&args.output, build_report(&name, &chunks, &config))
+        .map_err(|e| format!("Could not write {}: {}", args.output.display(), e))
+}
+
+fn run_hits(args: HitsArgs) -> Result<(), String> {
+    let mut chunks = Vec::new();
+    parse_tdcpix_txt(&args.input, &mut chunks)?;
+    let chunk_range = match (args.first, args.last) {
+        (None, None) => None,
+        (first, last) => Some((
+            first.unwrap_or(0),
+            last.unwrap_or(chunks.len().saturating_sub(1)),
+        )),
+    };
+    let selection = HitSelection {
+        chunks: chunk_range,
+        pixel: args.pixel,
+        time_window: None,
+    };
+    let words = export_hits(&chunks, &selection, args.format, &args.output)?;
+    println!("Wrote {} words to {}", words, args.output.display());
+    Ok(())
+}
@@ -0,0 +1,130 @@
+use eframe::egui::Color32;
+
+use crate::calibration::{Calibration, CalibrationStatus};
+use crate::tdcpix::{DataWord, MAX_DURATION_PS, QCHIP_COLUMNS};
+
+pub const HIT_COLOR: Color32 = Color32::from_rgb(0, 255, 0);
+pub const PILEUP_COLOR: Color32 = Color32::from_rgb(255, 0, 0);
+const MASKED_COLOR: Color32 = Color32::from_rgb(127, 127, 127);
+const UNCALIBRATED_COLOR: Color32 = Color32::from_rgb(255, 127, 14);
+
+// Distinct colors for categories (matplotlib tab10)
+const CATEGORY_COLORS: [Color32; 10] = [
+    Color32::from_rgb(31, 119, 180),
+    Color32::from_rgb(255, 127, 14),
+    Color32::from_rgb(44, 160, 44),
+    Color32::from_rgb(214, 39, 40),
+    Color32::from_rgb(148, 103, 189),
+    Color32::from_rgb(140, 86, 75),
+    Color32::from_rgb(227, 119, 194),
+    Color32::from_rgb(127, 127, 127),
+    Color32::from_rgb(188, 189, 34),
+    Color32::from_rgb(23, 190, 207),
+];
+
+// Stops of the gradient used for continuous values (viridis)
+const GRADIENT: [Color32; 5] = [
+    Color32::from_rgb(68, 1, 84),
+    Color32::from_rgb(59, 82, 139),
+    Color32::from_rgb(33, 145, 140),
+    Color32::from_rgb(94, 201, 98),
+    Color32::from_rgb(253, 231, 37),
+];
+
+/// What the color of a word on the timeline and pixel grid tells.
+#[derive(Clone, Copy, PartialEq, Debug, clap::ValueEnum)]
+pub enum ColorMode {
+    Tot,
+    Pileup,
+    Column,
+    QuarterChip,
+    FineTime,
+    // Whether the pixel is in the loaded calibration
+    Calibration,
+}
+
+impl ColorMode {
+    pub const ALL: [ColorMode; 6] = [
+        ColorMode::Tot,
+        ColorMode::Pileup,
+        ColorMode::Column,
+        ColorMode::QuarterChip,
+        ColorMode::FineTime,
+        ColorMode::Calibration,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            ColorMode::Tot => "ToT",
+            ColorMode::Pileup => "Pileup",
+            ColorMode::Column => "Column",
+            ColorMode::QuarterChip => "Quarter-chip",
+            ColorMode::FineTime => "Fine time",
+            ColorMode::Calibration => "Calibration",
+        }
+    }
+
+    pub fn color(&self, dw: &DataWord, calibration: &Calibration) -> Color32 {
+        let (x, _) = dw.pixel();
+        match self {
+            ColorMode::Tot => gradient(dw.get_duration() as f32 / MAX_DURATION_PS as f32),
+            ColorMode::Pileup if dw.address_pileup != 0 => PILEUP_COLOR,
+            ColorMode::Pileup => HIT_COLOR,
+            ColorMode::Column => category(x as usize),
+            ColorMode::QuarterChip => category((x / QCHIP_COLUMNS) as usize),
+            ColorMode::FineTime => gradient(dw.leading_fine_time as f32 / 31.0),
+            ColorMode::Calibration => match calibration.status(dw.pixel()) {
+                CalibrationStatus::Calibrated => HIT_COLOR,
+                CalibrationStatus::Masked => MASKED_COLOR,
+                CalibrationStatus::Missing => UNCALIBRATED_COLOR,
+            },
+        }
+    }
+
+    /// Labelled colors explaining the mode.
+    pub fn legend(&self) -> Vec<(String, Color32)> {
+        let stops = |max: f32, unit: &str| {
+            (0..GRADIENT.len())
+                .map(|i| {
+                    let f = i as f32 / (GRADIENT.len() - 1) as f32;
+                    (format!("{:.0}{}", f * max, unit), gradient(f))
+                })
+                .collect()
+        };
+        match self {
+            ColorMode::Tot => stops(MAX_DURATION_PS as f32 / 1_000.0, " ns"),
+            ColorMode::Pileup => vec![
+                ("hit".to_string(), HIT_COLOR),
+                ("pileup".to_string(), PILEUP_COLOR),
+            ],
+            ColorMode::Column => (0..QCHIP_COLUMNS)
+                .map(|x| (format!("x{}", x), category(x as usize)))
+                .collect(),
+            ColorMode::QuarterChip => (0..4).map(|q| (format!("q{}", q), category(q))).collect(),
+            ColorMode::FineTime => stops(31.0, ""),
+            ColorMode::Calibration => vec![
+                ("calibrated".to_string(), HIT_COLOR),
+                ("masked".to_string(), MASKED_COLOR),
+                ("missing".to_string(), UNCALIBRATED_COLOR),
+            ],
+        }
+    }
+}
+
+pub fn category(i: usize) -> Color32 {
+    CATEGORY_COLORS[i % CATEGORY_COLORS.len()]
+}
+
+/// Color for `f` in [0, 1] on the gradient.
+pub fn gradient(f: f32) -> Color32 {
+    let pos = f.clamp(0.0, 1.0) * (GRADIENT.len() - 1) as f32;
+    let i = (pos.floor() as usize).min(GRADIENT.len() - 2);
+    let t = pos - i as f32;
+    mix(GRADIENT[i], GRADIENT[i + 1], t)
+}
+
+/// Blends from `a` at `t` = 0 to `b` at `t` = 1.
+pub fn mix(a: Color32, b: Color32, t: f32) -> Color32 {
+    let lerp = |a: u8, b: u8| (a as f32 + (b as f32 - a as f32) * t).round() as u8;
+    Color32::from_rgb(lerp(a.r(), b.r()), lerp(a.g(), b.g()), lerp(a.b(), b.b()))
+}
@@ -0,0 +1,589 @@
+use image::codecs::gif::{GifEncoder, Repeat};
+use image::{Delay, Frame};
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+use crate::hitmap::{Afterglow, HitMap, CHIP_COLUMNS, CHIP_ROWS};
+use crate::render::{draw_view, render_chunk, Canvas, RenderSettings, Svg, BACKGROUND};
+use crate::tdcpix::{frame_offset, Chunk, DataWord, FRAME_PERIOD_PS};
+use crate::widgets::pixelgrid::GridContent;
+use crate::widgets::timeline::shown_range;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ViewFormat {
+    Png,
+    Svg,
+}
+
+impl ViewFormat {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ViewFormat::Png => "png",
+            ViewFormat::Svg => "svg",
+        }
+    }
+}
+
+/// Writes chunk `idx` with the grid showing `content` to `path`, as a png
+/// or as a vector svg.
+pub fn export_view(
+    settings: &RenderSettings,
+    chunks: &[Chunk],
+    idx: usize,
+    content: &GridContent,
+    format: ViewFormat,
+    path: &Path,
+) -> Result<(), String> {
+    if idx >= chunks.len() {
+        return Err(format!("There is no chunk {} to export", idx));
+    }
+    let (width, height) = (settings.width, settings.height());
+    match format {
+        ViewFormat::Png => {
+            let mut canvas = Canvas::new(width, height, BACKGROUND);
+            draw_view(&mut canvas, settings, chunks, idx, content);
+            canvas.image.save(path).map_err(|e| e.to_string())
+        }
+        ViewFormat::Svg => {
+            let mut svg = Svg::new(width, height, BACKGROUND);
+            draw_view(&mut svg, settings, chunks, idx, content);
+            std::fs::write(path, svg.finish()).map_err(|e| e.to_string())
+        }
+    }
+    .map_err(|e| format!("Could not write {}: {}", path.display(), e))
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum AnimationFormat {
+    Gif,
+    // One numbered png per chunk next to the chosen file
+    PngSequence,
+}
+
+/// A range of chunks played back the same way as in the window.
+#[derive(Clone, Debug)]
+pub struct AnimationConfig {
+    // Chunk indices, both included
+    pub first: usize,
+    pub last: usize,
+    pub fps: f32,
+    pub format: AnimationFormat,
+    // None renders every chunk on its own, without afterglow
+    pub afterglow_half_life: Option<f32>,
+}
+
+/// Checks that chunks `first..=last` are all loaded.
+fn check_chunk_range(first: usize, last: usize, chunks: &[Chunk]) -> Result<(), String> {
+    if first > last || last >= chunks.len() {
+        return Err(format!(
+            "Chunk range {}..={} is outside the {} loaded chunks",
+            first,
+            last,
+            chunks.len()
+        ));
+    }
+    Ok(())
+}
+
+/// Path of frame `n` of a png sequence, `run.png` becomes `run_00000.png`, ...
+pub fn sequence_path(path: &Path, n: usize) -> PathBuf {
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    path.with_file_name(format!("{}_{:05}.png", stem, n))
+}
+
+/// Renders the chunks of `config` one frame each and writes them to `path`,
+/// counting the frames written in `progress`. Returns the number of frames.
+pub fn export_animation(
+    chunks: &[Chunk],
+    settings: &RenderSettings,
+    config: &AnimationConfig,
+    path: &Path,
+    progress: &AtomicUsize,
+) -> Result<usize, String> {
+    check_chunk_range(config.first, config.last, chunks)?;
+    let write_error =
+        |e: &dyn std::fmt::Display| format!("Could not write {}: {}", path.display(), e);
+
+    let mut gif = match config.format {
+        AnimationFormat::Gif => {
+            let file = File::create(path).map_err(|e| write_error(&e))?;
+            // Speed 10 is the encoder's default trade-off between palette quality and time
+            let mut encoder = GifEncoder::new_with_speed(file, 10);
+            encoder
+                .set_repeat(Repeat::Infinite)
+                .map_err(|e| write_error(&e))?;
+            Some(encoder)
+        }
+        AnimationFormat::PngSequence => None,
+    };
+    let delay = Delay::from_saturating_duration(Duration::from_secs_f32(1.0 / config.fps.max(0.1)));
+
+    let mut afterglow = Afterglow::new(CHIP_COLUMNS, CHIP_ROWS);
+    for (n, idx) in (config.first..=config.last).enumerate() {
+        // Same decay per chunk as `TDCpixApp::advance_playback`
+        if let Some(half_life) = config.afterglow_half_life.filter(|_| idx > config.first) {
+            afterglow.add(
+                &HitMap::from_chunk(&chunks[idx - 1]),
+                0.5f32.powf(1.0 / half_life),
+            );
+        }
+        let glow = config.afterglow_half_life.map(|_| &afterglow);
+        let image = render_chunk(settings, chunks, idx, glow);
+
+        match &mut gif {
+            Some(encoder) => encoder
+                .encode_frame(Frame::from_parts(image, 0, 0, delay))
+                .map_err(|e| write_error(&e))?,
+            None => {
+                let frame_path = sequence_path(path, n);
+                image
+                    .save(&frame_path)
+                    .map_err(|e| format!("Could not write {}: {}", frame_path.display(), e))?;
+            }
+        }
+        progress.store(n + 1, Ordering::Relaxed);
+    }
+
+    Ok(config.last - config.first + 1)
+}
+
+/// An animation written by `export_animation` in a background thread, so
+/// the window stays responsive while the frames are rendered.
+pub struct AnimationExport {
+    pub path: PathBuf,
+    pub frames: usize,
+    progress: Arc<AtomicUsize>,
+    thread: Option<JoinHandle<Result<usize, String>>>,
+}
+
+impl AnimationExport {
+    /// Starts writing the animation, the thread works on its own copy of the
+    /// chunks the frames show.
+    pub fn start(
+        chunks: &[Chunk],
+        settings: &RenderSettings,
+        config: &AnimationConfig,
+        path: PathBuf,
+    ) -> Result<Self, String> {
+        check_chunk_range(config.first, config.last, chunks)?;
+        // The animated chunks and the timeline windows around the first and last
+        let window = settings.timeline_chunks;
+        let start = shown_range(chunks.len(), config.first, window).0;
+        let end = shown_range(chunks.len(), config.last, window).1;
+        let progress = Arc::new(AtomicUsize::new(0));
+        let thread = {
+            let chunks = chunks[start..end].to_vec();
+            let mut settings = settings.clone();
+            settings.first_chunk_idx += start;
+            let mut config = config.clone();
+            config.first -= start;
+            config.last -= start;
+            let path = path.clone();
+            let progress = progress.clone();
+            std::thread::spawn(move || {
+                export_animation(&chunks, &settings, &config, &path, &progress)
+            })
+        };
+        Ok(AnimationExport {
+            path,
+            frames: config.last - config.first + 1,
+            progress,
+            thread: Some(thread),
+        })
+    }
+
+    /// Frames written so far.
+    pub fn progress(&self) -> usize {
+        self.progress.load(Ordering::Relaxed)
+    }
+
+    /// The result of the export once the thread is done, None before that.
+    pub fn finished(&mut self) -> Option<Result<usize, String>> {
+        if !self.thread.as_ref()?.is_finished() {
+            return None;
+        }
+        let thread = self.thread.take()?;
+        Some(
+            thread
+                .join()
+                .unwrap_or_else(|_| Err(format!("Writing {} failed", self.path.display()))),
+        )
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, clap::ValueEnum)]
+pub enum HitsFormat {
+    Csv,
+    // One json object per line
+    JsonLines,
+}
+
+impl HitsFormat {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            HitsFormat::Csv => "csv",
+            HitsFormat::JsonLines => "jsonl",
+        }
+    }
+}
+
+/// Which decoded words are exported, everything by default.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct HitSelection {
+    // Chunk indices, both included
+    pub chunks: Option<(usize, usize)>,
+    pub pixel: Option<(u8, u8)>,
+    // Leading time window in ps relative to each frame
+    pub time_window: Option<(f64, f64)>,
+}
+
+impl HitSelection {
+    fn matches(&self, dw: &DataWord) -> bool {
+        self.pixel.is_none_or(|p| dw.pixel() == p)
+            && self
+                .time_window
+                .is_none_or(|(start, end)| (start..=end).contains(&(dw.get_start_time() as f64)))
+    }
+}
+
+/// Names of the exported values, in the order of `hit_values`.
+pub const HIT_COLUMNS: [&str; 19] = [
+    "chunk_idx",
+    "frame_counter",
+    "word_idx",
+    "raw",
+    "data_selector",
+    "address",
+    "address_arbiter",
+    "address_pileup",
+    "leading_coarse_time_selector",
+    "leading_coarse_time",
+    "leading_fine_time",
+    "trailing_coarse_time_selector",
+    "trailing_coarse_time",
+    "trailing_fine_time",
+    "x",
+    "y",
+    "start_time_ps",
+    "absolute_time_ps",
+    "tot_ps",
+];
+
+/// Decoded fields of word `word_idx` of chunk `chunk_idx`, which is `frames`
+/// frames after frame 0.
+fn hit_values(chunk_idx: usize, chunk: &Chunk, frames: u64, word_idx: usize) -> [u64; 19] {
+    let dw = &chunk.data_words[word_idx];
+    let frame_counter = chunk.frame_word.frame_counter;
+    let (x, y) = dw.pixel();
+    let start = dw.get_start_time();
+    [
+        chunk_idx as u64,
+        frame_counter as u64,
+        word_idx as u64,
+        dw.raw,
+        dw.data_selector as u64,
+        dw.address as u64,
+        dw.address_arbiter as u64,
+        dw.address_pileup as u64,
+        dw.leading_coarse_time_selector as u64,
+        dw.leading_coarse_time as u64,
+        dw.leading_fine_time as u64,
+        dw.trailing_coarse_time_selector as u64,
+        dw.trailing_coarse_time as u64,
+        dw.trailing_fine_time as u64,
+        x as u64,
+        y as u64,
+        start,
+        frames * FRAME_PERIOD_PS + start,
+        dw.get_duration(),
+    ]
+}
+
+/// Writes the words of `chunks` matching `selection` to `out`, one per line.
+/// Returns the number of words written.
+pub fn write_hits(
+    chunks: &[Chunk],
+    selection: &HitSelection,
+    format: HitsFormat,
+    out: &mut impl Write,
+) -> std::io::Result<usize> {
+    let (first, last) = selection
+        .chunks
+        .unwrap_or((0, chunks.len().saturating_sub(1)));
+    if selection.chunks.is_some() {
+        check_chunk_range(first, last, chunks)
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidInput, e))?;
+    }
+    if format == HitsFormat::Csv {
+        writeln!(out, "{}", HIT_COLUMNS.join(","))?;
+    }
+    let mut written = 0;
+    // Absolute times count from frame 0 of the first chunk's counter and go on
+    // across wraps of the counter, so they are followed from the first chunk
+    let mut frames = chunks
+        .first()
+        .map_or(0, |c| c.frame_word.frame_counter as i64);
+    for (chunk_idx, chunk) in chunks.iter().enumerate().take(last + 1) {
+        if chunk_idx > 0 {
+            let previous = chunks[chunk_idx - 1].frame_word.frame_counter;
+            frames += frame_offset(chunk.frame_word.frame_counter, previous);
+        }
+        if chunk_idx < first {
+            continue;
+        }
+        for (word_idx, dw) in chunk.data_words.iter().enumerate() {
+            if !selection.matches(dw) {
+                continue;
+            }
+            let values = hit_values(chunk_idx, chunk, frames.max(0) as u64, word_idx);
+            match format {
+                HitsFormat::Csv => {
+                    let fields: Vec<String> = values.iter().map(|v| v.to_string()).collect();
+                    writeln!(out, "{}", fields.join(","))?;
+                }
+                HitsFormat::JsonLines => {
+                    let fields: Vec<String> = HIT_COLUMNS
+                        .iter()
+                        .zip(values)
+                        .map(|(name, v)| format!("\"{}\":{}", name, v))
+                        .collect();
+                    writeln!(out, "{{{}}}", fields.join(","))?;
+                }
+            }
+            written += 1;
+        }
+    }
+    Ok(written)
+}
+
+/// Writes the selected words to `path`, see `write_hits`.
+pub fn export_hits(
+    chunks: &[Chunk],
+    selection: &HitSelection,
+    format: HitsFormat,
+    path: &Path,
+) -> Result<usize, String> {
+    if let Some((first, last)) = selection.chunks {
+        check_chunk_range(first, last, chunks)?;
+    }
+    File::create(path)
+        .map(BufWriter::new)
+        .and_then(|mut out| {
+            let written = write_hits(chunks, selection, format, &mut out)?;
+            out.flush()?;
+            Ok(written)
+        })
+        .map_err(|e| format!("Could not write {}: {}", path.display(), e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::colors::ColorMode;
+    use crate::tdcpix::COARSE_TIME_PS;
+    use crate::widgets::timeline::LaneGrouping;
+
+    fn test_chunks() -> Vec<Chunk> {
+        vec![
+            Chunk::new(vec![DataWord::for_pixel(1, 2, 2 * COARSE_TIME_PS, 0)], 7),
+            Chunk::new(
+                vec![
+                    DataWord::for_pixel(3, 4, 10 * COARSE_TIME_PS, 4 * COARSE_TIME_PS),
+                    DataWord::for_pixel(1, 2, 20 * COARSE_TIME_PS, 0),
+                ],
+                8,
+            ),
+        ]
+    }
+
+    #[test]
+    fn sequence_paths_number_the_frames_next_to_the_file() {
+        let path = Path::new("/data/run.gif");
+        assert_eq!(sequence_path(path, 0), Path::new("/data/run_00000.png"));
+        assert_eq!(sequence_path(path, 123), Path::new("/data/run_00123.png"));
+        assert_eq!(
+            sequence_path(Path::new("run"), 7),
+            Path::new("run_00007.png")
+        );
+    }
+
+    fn test_settings() -> RenderSettings {
+        RenderSettings {
+            width: 300,
+            include_timeline: true,
+            color_mode: ColorMode::Tot,
+            grouping: LaneGrouping::Pixel,
+            timeline_chunks: 2,
+            timeline_view: None,
+            file_name: None,
+            calibration: Default::default(),
+            first_chunk_idx: 0,
+        }
+    }
+
+    #[test]
+    fn animation_writes_one_png_per_chunk() {
+        let dir = std::env::temp_dir().join(format!("tdcpix_export_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("anim.png");
+        let settings = test_settings();
+        let config = AnimationConfig {
+            first: 0,
+            last: 1,
+            fps: 10.0,
+            format: AnimationFormat::PngSequence,
+            afterglow_half_life: Some(3.0),
+        };
+        let progress = AtomicUsize::new(0);
+        let frames = export_animation(&test_chunks(), &settings, &config, &path, &progress);
+        assert_eq!(frames, Ok(2));
+        assert_eq!(progress.load(Ordering::Relaxed), 2);
+        assert!(sequence_path(&path, 1).exists());
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn background_animation_matches_one_from_all_chunks() {
+        let dir = std::env::temp_dir().join(format!("tdcpix_window_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let chunks: Vec<Chunk> = (0..12)
+            .map(|i| {
+                let dw = DataWord::for_pixel(i as u8 % 10, 2, i * COARSE_TIME_PS, COARSE_TIME_PS);
+                Chunk::new(vec![dw], 100 + i as u32)
+            })
+            .collect();
+        let mut settings = test_settings();
+        settings.timeline_chunks = 5;
+        let config = AnimationConfig {
+            first: 4,
+            last: 6,
+            fps: 10.0,
+            format: AnimationFormat::PngSequence,
+            afterglow_half_life: Some(3.0),
+        };
+
+        let all_path = dir.join("all.png");
+        let progress = AtomicUsize::new(0);
+        export_animation(&chunks, &settings, &config, &all_path, &progress).unwrap();
+        let window_path = dir.join("window.png");
+        let mut export =
+            AnimationExport::start(&chunks, &settings, &config, window_path.clone()).unwrap();
+        while export.finished().is_none() {
+            std::thread::sleep(Duration::from_millis(10));
+        }
+
+        for n in 0..3 {
+            let read = |path: &Path| std::fs::read(sequence_path(path, n)).unwrap();
+            assert!(read(&all_path) == read(&window_path), "frame {}", n);
+        }
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    fn hits_text(chunks: &[Chunk], selection: &HitSelection, format: HitsFormat) -> String {
+        let mut out = Vec::new();
+        write_hits(chunks, selection, format, &mut out).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn csv_has_a_header_and_one_row_per_word() {
+        let text = hits_text(&test_chunks(), &HitSelection::default(), HitsFormat::Csv);
+        let lines: Vec<&str> = text.lines().collect();
+        assert_eq!(lines.len(), 4);
+        assert_eq!(lines[0], HIT_COLUMNS.join(","));
+
+        let row: Vec<&str> = lines[2].split(',').collect();
+        assert_eq!(row.len(), HIT_COLUMNS.len());
+        let column = |name| row[HIT_COLUMNS.iter().position(|c| *c == name).unwrap()];
+        assert_eq!(column("chunk_idx"), "1");
+        assert_eq!(column("frame_counter"), "8");
+        assert_eq!(column("word_idx"), "0");
+        assert_eq!((column("x"), column("y")), ("3", "4"));
+        assert_eq!(column("start_time_ps"), (10 * COARSE_TIME_PS).to_string());
+        assert_eq!(
+            column("absolute_time_ps"),
+            (8 * FRAME_PERIOD_PS + 10 * COARSE_TIME_PS).to_string()
+        );
+        assert_eq!(column("tot_ps"), (4 * COARSE_TIME_PS).to_string());
+    }
+
+    #[test]
+    fn absolute_time_continues_across_a_counter_wrap() {
+        let last_frame = (1 << 28) - 1;
+        let chunks = vec![
+            Chunk::new(vec![DataWord::for_pixel(1, 2, 0, 0)], last_frame),
+            Chunk::new(vec![DataWord::for_pixel(1, 2, 0, 0)], 0),
+            Chunk::new(vec![DataWord::for_pixel(1, 2, COARSE_TIME_PS, 0)], 2),
+        ];
+        let selection = HitSelection {
+            chunks: Some((1, 2)),
+            ..Default::default()
+        };
+        let text = hits_text(&chunks, &selection, HitsFormat::Csv);
+        let column = HIT_COLUMNS
+            .iter()
+            .position(|c| *c == "absolute_time_ps")
+            .unwrap();
+        let times: Vec<u64> = text
+            .lines()
+            .skip(1)
+            .map(|line| line.split(',').nth(column).unwrap().parse().unwrap())
+            .collect();
+        assert_eq!(
+            times,
+            [
+                (1 << 28) * FRAME_PERIOD_PS,
+                ((1 << 28) + 2) * FRAME_PERIOD_PS + COARSE_TIME_PS
+            ]
+        );
+    }
+
+    #[test]
+    fn json_lines_name_every_column() {
+        let text = hits_text(
+            &test_chunks(),
+            &HitSelection::default(),
+            HitsFormat::JsonLines,
+        );
+        let lines: Vec<&str> = text.lines().collect();
+        assert_eq!(lines.len(), 3);
+        assert!(lines[0].starts_with("{\"chunk_idx\":0,\"frame_counter\":7,\"word_idx\":0,"));
+        assert!(lines[0].ends_with('}'));
+        for name in HIT_COLUMNS {
+            assert!(lines[1].contains(&format!("\"{}\":", name)));
+        }
+    }
+
+    #[test]
+    fn selection_limits_chunks_and_pixel() {
+        let chunks = test_chunks();
+        let selection = HitSelection {
+            chunks: Some((1, 1)),
+            pixel: Some((1, 2)),
+            time_window: None,
+        };
+        let text = hits_text(&chunks, &selection, HitsFormat::Csv);
+        let rows: Vec<&str> = text.lines().skip(1).collect();
+        assert_eq!(rows.len(), 1);
+        assert!(rows[0].starts_with("1,8,1,"));
+    }
+
+    #[test]
+    fn out_of_range_chunks_are_an_error() {
+        let chunks = test_chunks();
+        for range in [(0, 2), (1, 0)] {
+            let selection = HitSelection {
+                chunks: Some(range),
+                ..Default::default()
+            };
+            let mut out = Vec::new();
+            assert!(write_hits(&chunks, &selection, HitsFormat::Csv, &mut out).is_err());
+            assert!(out.is_empty());
+        }
+    }
+}
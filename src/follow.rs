@@ -0,0 +1,109 @@
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom};
+use std::path::PathBuf;
+
+use crate::tdcpix::{parse_tdcpix_line, Chunk};
+
+/// Tails a capture file that is still being written and hands out the chunks
+/// of newly completed lines.
+pub struct FileFollower {
+    pub path: PathBuf,
+    // Bytes of the file consumed so far
+    offset: u64,
+    // Start of a line that has not been terminated yet
+    partial: String,
+    // Complete lines that could not be parsed and were skipped
+    pub bad_lines: u64,
+    // Why the last poll failed
+    pub error: Option<String>,
+}
+
+pub enum FollowUpdate {
+    Appended(Vec<Chunk>),
+    // The file got shorter, it was most likely rewritten from the start
+    Truncated,
+}
+
+impl FileFollower {
+    pub fn new(path: PathBuf) -> Self {
+        FileFollower {
+            path,
+            offset: 0,
+            partial: String::new(),
+            bad_lines: 0,
+            error: None,
+        }
+    }
+
+    pub fn poll(&mut self) -> std::io::Result<FollowUpdate> {
+        let mut file = File::open(&self.path)?;
+        let len = file.metadata()?.len();
+        if len < self.offset {
+            self.offset = 0;
+            self.partial.clear();
+            return Ok(FollowUpdate::Truncated);
+        }
+        if len == self.offset {
+            return Ok(FollowUpdate::Appended(Vec::new()));
+        }
+
+        file.seek(SeekFrom::Start(self.offset))?;
+        let mut new_bytes = Vec::with_capacity((len - self.offset) as usize);
+        file.take(len - self.offset).read_to_end(&mut new_bytes)?;
+        self.offset += new_bytes.len() as u64;
+        self.partial.push_str(&String::from_utf8_lossy(&new_bytes));
+
+        // Only complete lines are parsed, the rest waits for the next poll
+        let mut chunks = Vec::new();
+        if let Some(end) = self.partial.rfind('\n') {
+            for line in self.partial[..end].lines() {
+                match parse_tdcpix_line(line) {
+                    Ok(chunk) => chunks.extend(chunk),
+                    Err(_) => self.bad_lines += 1,
+                }
+            }
+            self.partial.drain(..=end);
+        }
+        Ok(FollowUpdate::Appended(chunks))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tdcpix::DataWord;
+
+    fn appended(follower: &mut FileFollower) -> Vec<Chunk> {
+        match follower.poll().unwrap() {
+            FollowUpdate::Appended(chunks) => chunks,
+            FollowUpdate::Truncated => panic!("unexpected truncation"),
+        }
+    }
+
+    #[test]
+    fn complete_lines_are_read_and_bad_ones_counted() {
+        let path = std::env::temp_dir().join(format!("tdcpix_follow_{}.txt", std::process::id()));
+        let line = |fc| Chunk::new(vec![DataWord::for_pixel(1, 2, 0, 0)], fc).to_line();
+        let (first, second) = (line(1), line(2));
+        let (second_start, second_end) = second.split_at(10);
+        std::fs::write(&path, format!("{}\nnot a chunk\n{}", first, second_start)).unwrap();
+
+        let mut follower = FileFollower::new(path.clone());
+        let chunks = appended(&mut follower);
+        assert_eq!(chunks.len(), 1);
+        assert_eq!(follower.bad_lines, 1);
+
+        let mut file = std::fs::OpenOptions::new()
+            .append(true)
+            .open(&path)
+            .unwrap();
+        std::io::Write::write_all(&mut file, format!("{}\n", second_end).as_bytes()).unwrap();
+        let chunks = appended(&mut follower);
+        assert_eq!(chunks.len(), 1);
+        assert_eq!(chunks[0].frame_word.frame_counter, 2);
+
+        std::fs::write(&path, "").unwrap();
+        assert!(matches!(follower.poll(), Ok(FollowUpdate::Truncated)));
+        std::fs::remove_file(&path).unwrap();
+    }
+}
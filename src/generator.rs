@@ -0,0 +1,364 @@
+use std::collections::BTreeMap;
+use std::str::FromStr;
+
+use crate::tdcpix::{
+    pixel_to_address, Chunk, DataWord, COARSE_TIME_PS, FRAME_PERIOD_PS, HA_PR_COL, MAPPED_COLUMNS,
+    PIXELS_PR_ARBITER,
+};
+
+const ROWS: u8 = HA_PR_COL * PIXELS_PR_ARBITER;
+
+/// Small deterministic PRNG (splitmix64), so a seed always gives the same file.
+pub struct Rng {
+    state: u64,
+}
+
+impl Rng {
+    pub fn new(seed: u64) -> Self {
+        Rng { state: seed }
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9E3779B97F4A7C15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58476D1CE4E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D049BB133111EB);
+        z ^ (z >> 31)
+    }
+
+    /// Uniform in [0, 1)
+    pub fn next_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    /// Uniform in [0, n)
+    pub fn below(&mut self, n: u64) -> u64 {
+        if n == 0 {
+            return 0;
+        }
+        self.next_u64() % n
+    }
+
+    /// Standard normal (Box-Muller)
+    pub fn normal(&mut self) -> f64 {
+        let u1 = self.next_f64().max(f64::MIN_POSITIVE);
+        let u2 = self.next_f64();
+        (-2.0 * u1.ln()).sqrt() * (2.0 * std::f64::consts::PI * u2).cos()
+    }
+
+    /// Poisson distributed count (Knuth), fine for the small means used here
+    pub fn poisson(&mut self, mean: f64) -> u32 {
+        if mean <= 0.0 {
+            return 0;
+        }
+        let limit = (-mean).exp();
+        let mut k = 0;
+        let mut p = self.next_f64();
+        while p > limit {
+            k += 1;
+            p *= self.next_f64();
+        }
+        k
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum BeamProfile {
+    Uniform,
+    Gaussian {
+        x: f32,
+        y: f32,
+        sigma_x: f32,
+        sigma_y: f32,
+    },
+}
+
+// "uniform" or "gaussian:x,y,sigma_x,sigma_y"
+impl FromStr for BeamProfile {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.split_once(':') {
+            None if s == "uniform" => Ok(BeamProfile::Uniform),
+            Some(("gaussian", params)) => {
+                let v = parse_list::<f32>(params)?;
+                if v.len() != 4 {
+                    return Err("gaussian needs x,y,sigma_x,sigma_y".to_string());
+                }
+                Ok(BeamProfile::Gaussian {
+                    x: v[0],
+                    y: v[1],
+                    sigma_x: v[2],
+                    sigma_y: v[3],
+                })
+            }
+            _ => Err(format!("unknown beam profile '{}'", s)),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TestPulse {
+    Off,
+    Column(u8),
+    Row(u8),
+    Diagonal,
+    Checkerboard,
+}
+
+impl TestPulse {
+    pub fn fires(&self, x: u8, y: u8) -> bool {
+        match *self {
+            TestPulse::Off => false,
+            TestPulse::Column(c) => x == c,
+            TestPulse::Row(r) => y == r,
+            TestPulse::Diagonal => x == y % MAPPED_COLUMNS,
+            TestPulse::Checkerboard => (x + y).is_multiple_of(2),
+        }
+    }
+}
+
+// "off", "column:N", "row:N", "diagonal" or "checkerboard"
+impl FromStr for TestPulse {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let parse_idx = |v: &str| v.parse::<u8>().map_err(|e| e.to_string());
+        match s.split_once(':') {
+            Some(("column", c)) => Ok(TestPulse::Column(parse_idx(c)?)),
+            Some(("row", r)) => Ok(TestPulse::Row(parse_idx(r)?)),
+            None if s == "off" => Ok(TestPulse::Off),
+            None if s == "diagonal" => Ok(TestPulse::Diagonal),
+            None if s == "checkerboard" => Ok(TestPulse::Checkerboard),
+            _ => Err(format!("unknown test pulse pattern '{}'", s)),
+        }
+    }
+}
+
+/// Parses "x,y" into a pixel coordinate.
+pub fn parse_pixel(s: &str) -> Result<(u8, u8), String> {
+    let v = parse_list::<u8>(s)?;
+    if v.len() != 2 {
+        return Err(format!("expected x,y but got '{}'", s));
+    }
+    Ok((v[0], v[1]))
+}
+
+fn parse_list<T: FromStr>(s: &str) -> Result<Vec<T>, String>
+where
+    T::Err: std::fmt::Display,
+{
+    s.split(',')
+        .map(|v| v.trim().parse::<T>().map_err(|e| e.to_string()))
+        .collect()
+}
+
+#[derive(Clone, Debug)]
+pub struct GeneratorConfig {
+    pub seed: u64,
+    pub num_chunks: usize,
+    pub first_frame: u32,
+    // Mean number of beam hits per chunk
+    pub hits_per_chunk: f32,
+    pub beam: BeamProfile,
+    // Mean number of uniformly distributed noise hits per chunk
+    pub noise_per_chunk: f32,
+    // Chance that a hit arbiter flags pileup
+    pub pileup_probability: f32,
+    pub dead_pixels: Vec<(u8, u8)>,
+    pub test_pulse: TestPulse,
+    // A test pulse is injected every n chunks, 0 counts as 1. Use
+    // `TestPulse::Off` to turn it off
+    pub test_pulse_period: usize,
+    pub tot_mean_ps: f32,
+    pub tot_sigma_ps: f32,
+}
+
+impl Default for GeneratorConfig {
+    fn default() -> Self {
+        // Mirrors what gen_chunky_chunk.py used to produce
+        GeneratorConfig {
+            seed: 0,
+            num_chunks: 10,
+            first_frame: 0,
+            hits_per_chunk: 3.5,
+            beam: BeamProfile::Uniform,
+            noise_per_chunk: 0.0,
+            pileup_probability: 1.0 / 333.0,
+            dead_pixels: Vec::new(),
+            test_pulse: TestPulse::Off,
+            test_pulse_period: 1,
+            tot_mean_ps: 100_000.0,
+            tot_sigma_ps: 50_000.0,
+        }
+    }
+}
+
+pub fn generate(config: &GeneratorConfig) -> Vec<Chunk> {
+    let mut rng = Rng::new(config.seed);
+    (0..config.num_chunks)
+        .map(|i| {
+            let frame_counter = config.first_frame.wrapping_add(i as u32);
+            generate_chunk(config, &mut rng, i, frame_counter)
+        })
+        .collect()
+}
+
+fn generate_chunk(
+    config: &GeneratorConfig,
+    rng: &mut Rng,
+    chunk_nr: usize,
+    frame_counter: u32,
+) -> Chunk {
+    // (pixel, arbiter pattern, leading time in ps), the pixel gives the address
+    let mut hits: Vec<((u8, u8), u8, u64)> = Vec::new();
+    let mut add_hit = |pixel: (u8, u8), start_time: u64| {
+        if !config.dead_pixels.contains(&pixel) {
+            hits.push((pixel, pixel_to_address(pixel.0, pixel.1).1, start_time));
+        }
+    };
+
+    for _ in 0..rng.poisson(config.hits_per_chunk as f64) {
+        add_hit(beam_pixel(config.beam, rng), rng.below(FRAME_PERIOD_PS));
+    }
+    for _ in 0..rng.poisson(config.noise_per_chunk as f64) {
+        add_hit(
+            beam_pixel(BeamProfile::Uniform, rng),
+            rng.below(FRAME_PERIOD_PS),
+        );
+    }
+
+    // All pixels of a test pulse fire at the same time, so the ones sharing an
+    // arbiter end up in one word
+    if chunk_nr.is_multiple_of(config.test_pulse_period.max(1)) {
+        let pulse_time = rng.below(FRAME_PERIOD_PS);
+        let mut arbiters: BTreeMap<u8, ((u8, u8), u8)> = BTreeMap::new();
+        for x in 0..MAPPED_COLUMNS {
+            for y in 0..ROWS {
+                if config.test_pulse.fires(x, y) && !config.dead_pixels.contains(&(x, y)) {
+                    let (address, arbiter_bit) = pixel_to_address(x, y);
+                    arbiters.entry(address).or_insert(((x, y), 0)).1 |= arbiter_bit;
+                }
+            }
+        }
+        hits.extend(
+            arbiters
+                .into_values()
+                .map(|(pixel, pattern)| (pixel, pattern, pulse_time)),
+        );
+    }
+
+    let mut data_words: Vec<DataWord> = Vec::new();
+    for ((x, y), pattern, start_time) in hits {
+        let tot = (config.tot_mean_ps as f64 + rng.normal() * config.tot_sigma_ps as f64)
+            .max(COARSE_TIME_PS as f64) as u64;
+        let mut dw = DataWord::for_pixel(x, y, start_time, tot);
+        dw.address_arbiter = pattern;
+        dw.raw = dw.encode();
+        if rng.next_f64() < config.pileup_probability as f64 {
+            dw.address_pileup = 1 << rng.below(PIXELS_PR_ARBITER as u64);
+            dw.raw = dw.encode();
+        }
+        data_words.push(dw);
+    }
+    data_words.sort_by_key(|dw| dw.get_start_time());
+
+    Chunk::new(data_words, frame_counter)
+}
+
+fn beam_pixel(beam: BeamProfile, rng: &mut Rng) -> (u8, u8) {
+    match beam {
+        BeamProfile::Uniform => (
+            rng.below(MAPPED_COLUMNS as u64) as u8,
+            rng.below(ROWS as u64) as u8,
+        ),
+        BeamProfile::Gaussian {
+            x,
+            y,
+            sigma_x,
+            sigma_y,
+        } => {
+            let px = (x as f64 + rng.normal() * sigma_x as f64).round();
+            let py = (y as f64 + rng.normal() * sigma_y as f64).round();
+            (
+                px.clamp(0.0, (MAPPED_COLUMNS - 1) as f64) as u8,
+                py.clamp(0.0, (ROWS - 1) as f64) as u8,
+            )
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn words(chunks: &[Chunk]) -> impl Iterator<Item = &DataWord> {
+        chunks.iter().flat_map(|c| &c.data_words)
+    }
+
+    #[test]
+    fn a_seed_always_gives_the_same_chunks() {
+        let config = GeneratorConfig {
+            seed: 42,
+            ..Default::default()
+        };
+        let lines = |config: &GeneratorConfig| -> Vec<String> {
+            generate(config).iter().map(|c| c.to_line()).collect()
+        };
+        assert_eq!(lines(&config), lines(&config));
+        let other = GeneratorConfig {
+            seed: 43,
+            ..config.clone()
+        };
+        assert_ne!(lines(&config), lines(&other));
+    }
+
+    #[test]
+    fn hits_per_chunk_is_the_mean() {
+        let config = GeneratorConfig {
+            num_chunks: 10_000,
+            hits_per_chunk: 4.0,
+            first_frame: (1 << 28) - 5,
+            ..Default::default()
+        };
+        let chunks = generate(&config);
+        let mean = words(&chunks).count() as f64 / chunks.len() as f64;
+        assert!((mean - 4.0).abs() < 0.1, "{}", mean);
+        assert!(words(&chunks).all(|dw| dw.get_start_time() < FRAME_PERIOD_PS));
+        // The frame counter is 28 bits wide
+        assert_eq!(chunks[5].frame_word.frame_counter, 0);
+    }
+
+    #[test]
+    fn dead_pixels_never_fire() {
+        let dead = (3, 13);
+        let config = GeneratorConfig {
+            num_chunks: 1_000,
+            hits_per_chunk: 20.0,
+            dead_pixels: vec![dead],
+            test_pulse: TestPulse::Column(3),
+            ..Default::default()
+        };
+        let chunks = generate(&config);
+        assert!(words(&chunks).all(|dw| !dw.hit_pixels().contains(&dead)));
+        assert!(words(&chunks).any(|dw| dw.hit_pixels().contains(&(3, 12))));
+    }
+
+    #[test]
+    fn test_pulse_pixels_share_the_word_of_their_arbiter() {
+        let config = GeneratorConfig {
+            num_chunks: 1,
+            hits_per_chunk: 0.0,
+            pileup_probability: 0.0,
+            test_pulse: TestPulse::Column(2),
+            ..Default::default()
+        };
+        let chunk = &generate(&config)[0];
+        assert_eq!(chunk.data_words.len(), HA_PR_COL as usize);
+        for dw in &chunk.data_words {
+            assert_eq!(dw.address_arbiter, 0b11111);
+            assert_eq!(dw.hit_pixels().len(), PIXELS_PR_ARBITER as usize);
+            assert_eq!(dw.get_start_time(), chunk.data_words[0].get_start_time());
+        }
+    }
+}
@@ -0,0 +1,173 @@
+use crate::tdcpix::Chunk;
+
+// Full chip size in pixels
+pub const CHIP_COLUMNS: u8 = 40;
+pub const CHIP_ROWS: u8 = 45;
+
+// Flags stored per pixel
+pub const HIT: u8 = 1 << 0;
+pub const PILEUP: u8 = 1 << 1;
+
+// Marks a pixel without a word in `HitMap::words`
+const NO_WORD: u32 = u32::MAX;
+
+/// Per pixel flags of one chunk, indexed by (x, y) in constant time.
+#[derive(Clone)]
+pub struct HitMap {
+    pub columns: u8,
+    pub rows: u8,
+    flags: Vec<u8>,
+    // Index of the (last) data word that hit each pixel
+    words: Vec<u32>,
+}
+
+impl HitMap {
+    pub fn new(columns: u8, rows: u8) -> Self {
+        HitMap {
+            columns,
+            rows,
+            flags: vec![0; columns as usize * rows as usize],
+            words: vec![NO_WORD; columns as usize * rows as usize],
+        }
+    }
+
+    pub fn from_chunk(chunk: &Chunk) -> Self {
+        let mut map = HitMap::new(CHIP_COLUMNS, CHIP_ROWS);
+        for (word_idx, dw) in chunk.data_words.iter().enumerate() {
+            let (x, y) = dw.pixel();
+            map.set(x, y, HIT);
+            if let Some(i) = map.index(x, y) {
+                map.words[i] = word_idx as u32;
+            }
+            for (x, y) in dw.pileup_pixels() {
+                map.set(x, y, PILEUP);
+            }
+        }
+        map
+    }
+
+    pub fn clear(&mut self) {
+        self.flags.fill(0);
+        self.words.fill(NO_WORD);
+    }
+
+    fn index(&self, x: u8, y: u8) -> Option<usize> {
+        (x < self.columns && y < self.rows).then(|| y as usize * self.columns as usize + x as usize)
+    }
+
+    /// Adds `flag` to the pixel, pixels outside the map are ignored.
+    pub fn set(&mut self, x: u8, y: u8, flag: u8) {
+        if let Some(i) = self.index(x, y) {
+            self.flags[i] |= flag;
+        }
+    }
+
+    pub fn get(&self, x: u8, y: u8) -> u8 {
+        self.index(x, y).map_or(0, |i| self.flags[i])
+    }
+
+    /// Index of the data word that hit the pixel.
+    pub fn word_at(&self, x: u8, y: u8) -> Option<usize> {
+        self.index(x, y)
+            .map(|i| self.words[i])
+            .filter(|&w| w != NO_WORD)
+            .map(|w| w as usize)
+    }
+}
+
+/// Number of words hitting each pixel, summed over many chunks.
+#[derive(Clone)]
+pub struct HitCounts {
+    pub columns: u8,
+    pub rows: u8,
+    counts: Vec<u32>,
+}
+
+impl HitCounts {
+    pub fn new(columns: u8, rows: u8) -> Self {
+        HitCounts {
+            columns,
+            rows,
+            counts: vec![0; columns as usize * rows as usize],
+        }
+    }
+
+    pub fn from_chunks(chunks: &[Chunk]) -> Self {
+        let mut counts = HitCounts::new(CHIP_COLUMNS, CHIP_ROWS);
+        for chunk in chunks {
+            counts.add_chunk(chunk);
+        }
+        counts
+    }
+
+    /// Counts the pixel of every word in `chunk`, the same one the grid shows.
+    pub fn add_chunk(&mut self, chunk: &Chunk) {
+        for dw in &chunk.data_words {
+            let (x, y) = dw.pixel();
+            if x < self.columns && y < self.rows {
+                self.counts[y as usize * self.columns as usize + x as usize] += 1;
+            }
+        }
+    }
+
+    pub fn get(&self, x: u8, y: u8) -> u32 {
+        if x < self.columns && y < self.rows {
+            self.counts[y as usize * self.columns as usize + x as usize]
+        } else {
+            0
+        }
+    }
+
+    pub fn max(&self) -> u32 {
+        self.counts.iter().copied().max().unwrap_or(0)
+    }
+
+    pub fn total(&self) -> u64 {
+        self.counts.iter().map(|&c| c as u64).sum()
+    }
+}
+
+/// Fading trace of the hits of previously shown chunks.
+#[derive(Clone)]
+pub struct Afterglow {
+    columns: u8,
+    rows: u8,
+    // 1.0 for a pixel hit in the last chunk, fading towards 0.0
+    intensity: Vec<f32>,
+}
+
+impl Afterglow {
+    pub fn new(columns: u8, rows: u8) -> Self {
+        Afterglow {
+            columns,
+            rows,
+            intensity: vec![0.0; columns as usize * rows as usize],
+        }
+    }
+
+    /// Fades the trace by `factor` and lights up the pixels flagged in `map`.
+    pub fn add(&mut self, map: &HitMap, factor: f32) {
+        for v in &mut self.intensity {
+            *v *= factor;
+        }
+        for x in 0..self.columns.min(map.columns) {
+            for y in 0..self.rows.min(map.rows) {
+                if map.get(x, y) != 0 {
+                    self.intensity[y as usize * self.columns as usize + x as usize] = 1.0;
+                }
+            }
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.intensity.fill(0.0);
+    }
+
+    pub fn get(&self, x: u8, y: u8) -> f32 {
+        if x < self.columns && y < self.rows {
+            self.intensity[y as usize * self.columns as usize + x as usize]
+        } else {
+            0.0
+        }
+    }
+}
@@ -1,20 +1,45 @@
 #![allow(dead_code)]
 
+mod calibration;
+mod cli;
+mod colors;
+mod export;
+mod follow;
+mod generator;
+mod hitmap;
+mod net;
+mod render;
+mod replay;
+mod report;
+mod simulation;
+mod sliding;
+mod stats;
 mod tdcpix;
 mod tdcpixapp;
+mod validation;
 mod widgets;
 
+use clap::Parser;
 use tdcpixapp::TDCpixApp;
 
 use eframe::egui;
 use eframe::Theme;
 
 fn main() -> Result<(), eframe::Error> {
+    let args = cli::Cli::parse();
+    if let Some(command) = args.command {
+        if let Err(e) = cli::run(command) {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+        return Ok(());
+    }
+
     static W_DIM: egui::Vec2 = egui::Vec2::new(576.0, 768.0);
 
     let native_options = eframe::NativeOptions {
-        resizable: false,
         initial_window_size: Some(W_DIM),
+        min_window_size: Some(egui::vec2(320.0, 400.0)),
         default_theme: Theme::Dark,
         follow_system_theme: false,
         ..Default::default()
@@ -23,6 +48,6 @@ fn main() -> Result<(), eframe::Error> {
     eframe::run_native(
         "TDCpix data visualizer",
         native_options,
-        Box::new(|cc| Box::new(TDCpixApp::new(cc, W_DIM))),
+        Box::new(|cc| Box::new(TDCpixApp::new(cc))),
     )
 }
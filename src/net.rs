@@ -0,0 +1,458 @@
+use std::fs::File;
+use std::io::{BufWriter, ErrorKind, Read, Write};
+use std::net::{TcpListener, TcpStream, UdpSocket};
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+use crate::tdcpix::{is_frame_word, parse_tdcpix_line, Chunk, DataWord, FrameWord};
+
+// How often the socket thread checks if it should stop
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+// Bad lines in a row after which the sender is reported as broken
+const MAX_BAD_LINES: u32 = 10;
+// Longest hex line accepted, far more words than a frame can hold
+const MAX_LINE_LEN: usize = 1 << 20;
+
+#[derive(Clone, Copy, Debug, PartialEq, clap::ValueEnum)]
+pub enum Protocol {
+    Tcp,
+    Udp,
+}
+
+/// How TDCpix words are put on the wire.
+#[derive(Clone, Copy, Debug, PartialEq, clap::ValueEnum)]
+pub enum WireFormat {
+    // Same as the text files: hex words, one chunk per line ending with the frame word
+    Hex,
+    // 6 byte big endian words, a chunk ends with a word that has bits 47..45 set
+    Binary,
+}
+
+/// Puts a chunk on the wire, the inverse of `WordDecoder`.
+pub fn encode_chunk(chunk: &Chunk, format: WireFormat) -> Vec<u8> {
+    match format {
+        WireFormat::Hex => {
+            let mut line = chunk.to_line();
+            line.push('\n');
+            line.into_bytes()
+        }
+        WireFormat::Binary => chunk
+            .data_words
+            .iter()
+            .map(|dw| dw.raw)
+            .chain(std::iter::once(chunk.frame_word.raw))
+            .flat_map(|raw| raw.to_be_bytes()[2..].to_vec())
+            .collect(),
+    }
+}
+
+/// Writes chunks to a file as they are put on the wire, hex gives the text format.
+pub fn write_chunks(file: &Path, chunks: &[Chunk], format: WireFormat) -> std::io::Result<()> {
+    let mut writer = BufWriter::new(File::create(file)?);
+    for chunk in chunks {
+        writer.write_all(&encode_chunk(chunk, format))?;
+    }
+    writer.flush()
+}
+
+/// Turns a byte stream into chunks, bytes may be split anywhere.
+pub struct WordDecoder {
+    format: WireFormat,
+    buffer: Vec<u8>,
+    // Bytes at the start of `buffer` already searched for a newline
+    scanned: usize,
+    // Dropping the rest of a line that was too long
+    skipping_line: bool,
+    data_words: Vec<DataWord>,
+    pub words: u64,
+    // Hex lines that could not be parsed and were skipped
+    pub bad_lines: u64,
+    consecutive_bad_lines: u32,
+    last_error: Option<String>,
+}
+
+impl WordDecoder {
+    pub fn new(format: WireFormat) -> Self {
+        WordDecoder {
+            format,
+            buffer: Vec::new(),
+            scanned: 0,
+            skipping_line: false,
+            data_words: Vec::new(),
+            words: 0,
+            bad_lines: 0,
+            consecutive_bad_lines: 0,
+            last_error: None,
+        }
+    }
+
+    /// The last parse error, once too many lines in a row could not be parsed.
+    pub fn failure(&self) -> Option<&str> {
+        self.last_error
+            .as_deref()
+            .filter(|_| self.consecutive_bad_lines >= MAX_BAD_LINES)
+    }
+
+    pub fn feed(&mut self, bytes: &[u8]) -> Vec<Chunk> {
+        self.buffer.extend_from_slice(bytes);
+        let mut chunks = Vec::new();
+        match self.format {
+            WireFormat::Hex => {
+                let buffer = std::mem::take(&mut self.buffer);
+                let mut line_start = 0;
+                let mut scan_from = self.scanned;
+                while let Some(n) = buffer[scan_from..].iter().position(|&b| b == b'\n') {
+                    let end = scan_from + n;
+                    if self.skipping_line {
+                        self.skipping_line = false;
+                    } else {
+                        let line = String::from_utf8_lossy(&buffer[line_start..end]);
+                        chunks.extend(self.decode_line(&line));
+                    }
+                    line_start = end + 1;
+                    scan_from = line_start;
+                }
+                self.buffer = buffer;
+                self.buffer.drain(..line_start);
+                self.scanned = self.buffer.len();
+
+                if self.buffer.len() > MAX_LINE_LEN {
+                    if !self.skipping_line {
+                        self.line_failed(format!("Line longer than {} bytes", MAX_LINE_LEN));
+                    }
+                    self.skipping_line = true;
+                    self.buffer.clear();
+                    self.scanned = 0;
+                }
+            }
+            WireFormat::Binary => {
+                let complete = self.buffer.len() - self.buffer.len() % 6;
+                for word in self.buffer[..complete].chunks_exact(6) {
+                    let raw = word.iter().fold(0u64, |acc, &b| acc << 8 | b as u64);
+                    self.words += 1;
+                    if is_frame_word(raw) {
+                        chunks.push(Chunk {
+                            data_words: std::mem::take(&mut self.data_words),
+                            frame_word: FrameWord::from(raw),
+                        });
+                    } else {
+                        self.data_words.push(DataWord::from(raw));
+                    }
+                }
+                self.buffer.drain(..complete);
+            }
+        }
+        chunks
+    }
+
+    fn decode_line(&mut self, line: &str) -> Option<Chunk> {
+        match parse_tdcpix_line(line) {
+            Ok(Some(chunk)) => {
+                self.words += chunk.data_words.len() as u64 + 1;
+                self.consecutive_bad_lines = 0;
+                Some(chunk)
+            }
+            Ok(None) => None,
+            Err(e) => {
+                self.line_failed(e);
+                None
+            }
+        }
+    }
+
+    fn line_failed(&mut self, error: String) {
+        self.bad_lines += 1;
+        self.consecutive_bad_lines += 1;
+        self.last_error = Some(error);
+    }
+}
+
+#[derive(Clone, Debug)]
+pub enum ConnectionStatus {
+    Listening,
+    Connected(String),
+    Error(String),
+}
+
+struct Shared {
+    status: ConnectionStatus,
+    pending: Vec<Chunk>,
+    words: u64,
+    chunks: u64,
+    bad_lines: u64,
+}
+
+#[derive(Clone, Debug)]
+pub struct NetConfig {
+    pub protocol: Protocol,
+    pub format: WireFormat,
+    // Interface to listen on, 0.0.0.0 accepts senders from other machines
+    pub address: String,
+    pub port: u16,
+    // Number of most recent chunks kept in memory
+    pub capacity: usize,
+}
+
+impl Default for NetConfig {
+    fn default() -> Self {
+        NetConfig {
+            protocol: Protocol::Tcp,
+            format: WireFormat::Hex,
+            address: "127.0.0.1".to_string(),
+            port: 5000,
+            capacity: 10_000,
+        }
+    }
+}
+
+/// Receives TDCpix words on a socket in a background thread.
+pub struct NetSource {
+    shared: Arc<Mutex<Shared>>,
+    stop: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+    // (time, words, chunks) at the last rate update
+    last_sample: (Instant, u64, u64),
+    pub word_rate: f64,
+    pub chunk_rate: f64,
+}
+
+impl NetSource {
+    pub fn start(config: &NetConfig) -> Self {
+        let shared = Arc::new(Mutex::new(Shared {
+            status: ConnectionStatus::Listening,
+            pending: Vec::new(),
+            words: 0,
+            chunks: 0,
+            bad_lines: 0,
+        }));
+        let stop = Arc::new(AtomicBool::new(false));
+
+        let thread = {
+            let shared = shared.clone();
+            let stop = stop.clone();
+            let config = config.clone();
+            std::thread::spawn(move || {
+                let result = match config.protocol {
+                    Protocol::Tcp => receive_tcp(&config, &shared, &stop),
+                    Protocol::Udp => receive_udp(&config, &shared, &stop),
+                };
+                if let Err(e) = result {
+                    shared.lock().unwrap().status = ConnectionStatus::Error(e.to_string());
+                }
+            })
+        };
+
+        NetSource {
+            shared,
+            stop,
+            thread: Some(thread),
+            last_sample: (Instant::now(), 0, 0),
+            word_rate: 0.0,
+            chunk_rate: 0.0,
+        }
+    }
+
+    /// Chunks received since the last call.
+    pub fn take_chunks(&mut self) -> Vec<Chunk> {
+        let mut shared = self.shared.lock().unwrap();
+
+        let elapsed = self.last_sample.0.elapsed().as_secs_f64();
+        if elapsed >= 1.0 {
+            self.word_rate = (shared.words - self.last_sample.1) as f64 / elapsed;
+            self.chunk_rate = (shared.chunks - self.last_sample.2) as f64 / elapsed;
+            self.last_sample = (Instant::now(), shared.words, shared.chunks);
+        }
+
+        std::mem::take(&mut shared.pending)
+    }
+
+    pub fn status(&self) -> ConnectionStatus {
+        self.shared.lock().unwrap().status.clone()
+    }
+
+    pub fn totals(&self) -> (u64, u64) {
+        let shared = self.shared.lock().unwrap();
+        (shared.words, shared.chunks)
+    }
+
+    /// Received lines that were skipped because they could not be parsed.
+    pub fn bad_lines(&self) -> u64 {
+        self.shared.lock().unwrap().bad_lines
+    }
+}
+
+impl Drop for NetSource {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+fn publish(shared: &Mutex<Shared>, decoder: &mut WordDecoder, chunks: Vec<Chunk>, peer: &str) {
+    let mut shared = shared.lock().unwrap();
+    shared.words += decoder.words;
+    decoder.words = 0;
+    shared.bad_lines += decoder.bad_lines;
+    decoder.bad_lines = 0;
+    shared.chunks += chunks.len() as u64;
+    shared.pending.extend(chunks);
+    shared.status = match decoder.failure() {
+        Some(e) => ConnectionStatus::Error(format!(
+            "{} lines in a row from {} could not be parsed, last: {}",
+            MAX_BAD_LINES, peer, e
+        )),
+        None => ConnectionStatus::Connected(peer.to_string()),
+    };
+}
+
+fn receive_tcp(
+    config: &NetConfig,
+    shared: &Mutex<Shared>,
+    stop: &AtomicBool,
+) -> std::io::Result<()> {
+    let listener = TcpListener::bind((config.address.as_str(), config.port))?;
+    listener.set_nonblocking(true)?;
+
+    // Only failing to listen is fatal, a broken connection is reported and the
+    // next sender can connect
+    while !stop.load(Ordering::Relaxed) {
+        let (stream, peer) = match listener.accept() {
+            Ok(conn) => conn,
+            Err(e) => {
+                if e.kind() != ErrorKind::WouldBlock {
+                    shared.lock().unwrap().status =
+                        ConnectionStatus::Error(format!("Could not accept a connection: {}", e));
+                }
+                std::thread::sleep(POLL_INTERVAL);
+                continue;
+            }
+        };
+        let peer = peer.to_string();
+        shared.lock().unwrap().status = ConnectionStatus::Connected(peer.clone());
+        let status = match receive_connection(stream, &peer, config, shared, stop) {
+            Ok(()) => ConnectionStatus::Listening,
+            Err(e) => ConnectionStatus::Error(format!("Connection from {} lost: {}", peer, e)),
+        };
+        shared.lock().unwrap().status = status;
+    }
+    Ok(())
+}
+
+fn receive_connection(
+    mut stream: TcpStream,
+    peer: &str,
+    config: &NetConfig,
+    shared: &Mutex<Shared>,
+    stop: &AtomicBool,
+) -> std::io::Result<()> {
+    stream.set_nonblocking(false)?;
+    stream.set_read_timeout(Some(POLL_INTERVAL))?;
+
+    // A new connection starts a fresh stream
+    let mut decoder = WordDecoder::new(config.format);
+    let mut buf = [0u8; 64 * 1024];
+    while !stop.load(Ordering::Relaxed) {
+        match stream.read(&mut buf) {
+            Ok(0) => break,
+            Ok(n) => {
+                let chunks = decoder.feed(&buf[..n]);
+                publish(shared, &mut decoder, chunks, peer);
+            }
+            Err(e)
+                if matches!(
+                    e.kind(),
+                    ErrorKind::WouldBlock | ErrorKind::TimedOut | ErrorKind::Interrupted
+                ) => {}
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(())
+}
+
+fn receive_udp(
+    config: &NetConfig,
+    shared: &Mutex<Shared>,
+    stop: &AtomicBool,
+) -> std::io::Result<()> {
+    let socket = UdpSocket::bind((config.address.as_str(), config.port))?;
+    socket.set_read_timeout(Some(POLL_INTERVAL))?;
+
+    let mut decoder = WordDecoder::new(config.format);
+    let mut buf = [0u8; 64 * 1024];
+    while !stop.load(Ordering::Relaxed) {
+        match socket.recv_from(&mut buf) {
+            Ok((n, peer)) => {
+                let chunks = decoder.feed(&buf[..n]);
+                publish(shared, &mut decoder, chunks, &peer.to_string());
+            }
+            Err(e) if matches!(e.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) => {}
+            // E.g. an ICMP port unreachable from an earlier send, the socket still works
+            Err(e) => {
+                shared.lock().unwrap().status =
+                    ConnectionStatus::Error(format!("Could not receive: {}", e));
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_chunk(frame_counter: u32) -> Chunk {
+        Chunk::new(vec![DataWord::for_pixel(3, 4, 1000, 500)], frame_counter)
+    }
+
+    #[test]
+    fn hex_lines_split_across_reads() {
+        let bytes = [test_chunk(1), test_chunk(2)]
+            .iter()
+            .flat_map(|c| encode_chunk(c, WireFormat::Hex))
+            .collect::<Vec<u8>>();
+        let mut decoder = WordDecoder::new(WireFormat::Hex);
+        let mut chunks = Vec::new();
+        for piece in bytes.chunks(5) {
+            chunks.extend(decoder.feed(piece));
+        }
+        assert_eq!(chunks.len(), 2);
+        assert_eq!(chunks[1].frame_word.frame_counter, 2);
+        assert_eq!(decoder.words, 4);
+        assert_eq!(decoder.bad_lines, 0);
+    }
+
+    #[test]
+    fn binary_files_decode_to_the_written_chunks() {
+        let path = std::env::temp_dir().join(format!("tdcpix_binary_{}.bin", std::process::id()));
+        let chunks = [test_chunk(1), Chunk::new(Vec::new(), 2), test_chunk(3)];
+        write_chunks(&path, &chunks, WireFormat::Binary).unwrap();
+        let bytes = std::fs::read(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(bytes.len(), 5 * 6);
+
+        let decoded = WordDecoder::new(WireFormat::Binary).feed(&bytes);
+        let lines = |chunks: &[Chunk]| chunks.iter().map(|c| c.to_line()).collect::<Vec<_>>();
+        assert_eq!(lines(&decoded), lines(&chunks));
+    }
+
+    #[test]
+    fn overlong_hex_line_is_one_bad_line() {
+        let mut decoder = WordDecoder::new(WireFormat::Hex);
+        let garbage = vec![b'0'; 64 * 1024];
+        for _ in 0..(2 * MAX_LINE_LEN / garbage.len()) {
+            assert!(decoder.feed(&garbage).is_empty());
+            assert!(decoder.buffer.len() <= MAX_LINE_LEN);
+        }
+        let mut end = b"0\n".to_vec();
+        end.extend(encode_chunk(&test_chunk(7), WireFormat::Hex));
+        let chunks = decoder.feed(&end);
+        assert_eq!(decoder.bad_lines, 1);
+        assert_eq!(chunks.len(), 1);
+        assert_eq!(chunks[0].frame_word.frame_counter, 7);
+    }
+}
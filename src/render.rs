@@ -0,0 +1,602 @@
+use ab_glyph::{Font, FontArc, PxScale, ScaleFont};
+use eframe::egui::{self, Align2, Color32, Pos2, Rect};
+use image::RgbaImage;
+use std::sync::OnceLock;
+
+use crate::calibration::Calibration;
+use crate::colors::{category, gradient, ColorMode};
+use crate::hitmap::{Afterglow, HitCounts, HitMap, CHIP_COLUMNS, CHIP_ROWS};
+use crate::stats::Histogram;
+use crate::tdcpix::Chunk;
+use crate::widgets::pixel::HitType;
+use crate::widgets::pixelgrid::{GridContent, GridGeometry, SEPARATOR_COLOR};
+use crate::widgets::timeline::{self, tick_step, LaneGrouping, TimeView, TimelineLayout};
+
+pub const BACKGROUND: Color32 = Color32::from_rgb(27, 27, 27);
+pub const TEXT_COLOR: Color32 = Color32::from_rgb(200, 200, 200);
+
+/// Something the pixel grid and timeline can be drawn on, so the same drawing
+/// code serves the window and the exported images.
+pub trait Surface {
+    fn fill_rect(&mut self, rect: Rect, color: Color32);
+    fn line(&mut self, points: [Pos2; 2], width: f32, color: Color32);
+    fn text(&mut self, pos: Pos2, align: Align2, text: &str, size: f32, color: Color32);
+}
+
+impl Surface for egui::Painter {
+    fn fill_rect(&mut self, rect: Rect, color: Color32) {
+        self.rect_filled(rect, 0.0, color);
+    }
+
+    fn line(&mut self, points: [Pos2; 2], width: f32, color: Color32) {
+        self.line_segment(points, egui::Stroke::new(width, color));
+    }
+
+    fn text(&mut self, pos: Pos2, align: Align2, text: &str, size: f32, color: Color32) {
+        egui::Painter::text(
+            self,
+            pos,
+            align,
+            text,
+            egui::FontId::proportional(size),
+            color,
+        );
+    }
+}
+
+/// An RGBA image drawn on the CPU, with the same font as the window.
+pub struct Canvas {
+    pub image: RgbaImage,
+    font: FontArc,
+}
+
+/// The proportional font of the window, parsed on first use and shared by
+/// all canvases after that.
+fn window_font() -> FontArc {
+    static FONT: OnceLock<FontArc> = OnceLock::new();
+    FONT.get_or_init(|| {
+        let mut fonts = egui::FontDefinitions::default();
+        let data = fonts.font_data.remove("Ubuntu-Light").unwrap();
+        FontArc::try_from_vec(data.font.into_owned()).unwrap()
+    })
+    .clone()
+}
+
+impl Canvas {
+    pub fn new(width: u32, height: u32, background: Color32) -> Self {
+        let [r, g, b, a] = background.to_array();
+        Canvas {
+            image: RgbaImage::from_pixel(width, height, image::Rgba([r, g, b, a])),
+            font: window_font(),
+        }
+    }
+
+    pub fn rect(&self) -> Rect {
+        Rect::from_min_size(
+            Pos2::ZERO,
+            egui::vec2(self.image.width() as f32, self.image.height() as f32),
+        )
+    }
+
+    /// Blends a premultiplied `color` into pixel (x, y), `coverage` of it covered.
+    fn blend(&mut self, x: i64, y: i64, color: Color32, coverage: f32) {
+        if x < 0 || y < 0 || x >= self.image.width() as i64 || y >= self.image.height() as i64 {
+            return;
+        }
+        let dst = self.image.get_pixel_mut(x as u32, y as u32);
+        let src = color.to_array();
+        let keep = 1.0 - src[3] as f32 / 255.0 * coverage;
+        for (d, s) in dst.0.iter_mut().zip(src) {
+            *d = (s as f32 * coverage + *d as f32 * keep).round() as u8;
+        }
+    }
+}
+
+impl Surface for Canvas {
+    fn fill_rect(&mut self, rect: Rect, color: Color32) {
+        // Edge pixels are blended by how much of them the rect covers, so
+        // boxes thinner than a pixel still show up
+        let (x0, x1) = (rect.min.x.floor() as i64, rect.max.x.ceil() as i64);
+        let (y0, y1) = (rect.min.y.floor() as i64, rect.max.y.ceil() as i64);
+        let overlap = |p: i64, min: f32, max: f32| ((p + 1) as f32).min(max) - (p as f32).max(min);
+        for y in y0.max(0)..y1.min(self.image.height() as i64) {
+            let cov_y = overlap(y, rect.min.y, rect.max.y);
+            for x in x0.max(0)..x1.min(self.image.width() as i64) {
+                let cov = overlap(x, rect.min.x, rect.max.x) * cov_y;
+                if cov > 0.0 {
+                    self.blend(x, y, color, cov);
+                }
+            }
+        }
+    }
+
+    fn line(&mut self, [a, b]: [Pos2; 2], width: f32, color: Color32) {
+        if a.x == b.x || a.y == b.y {
+            let rect = Rect::from_two_pos(a, b).expand(width / 2.0);
+            self.fill_rect(rect, color);
+            return;
+        }
+        // Diagonal lines are stamped along their length
+        let steps = (b - a).length().ceil() as usize;
+        for i in 0..=steps {
+            let p = a + (b - a) * (i as f32 / steps as f32);
+            self.fill_rect(Rect::from_center_size(p, egui::vec2(width, width)), color);
+        }
+    }
+
+    fn text(&mut self, pos: Pos2, align: Align2, text: &str, size: f32, color: Color32) {
+        let font = self.font.clone();
+        let scaled = font.as_scaled(PxScale::from(size));
+
+        let mut glyphs = Vec::new();
+        let mut x = 0.0;
+        let mut last = None;
+        for c in text.chars() {
+            let id = scaled.glyph_id(c);
+            if let Some(last) = last {
+                x += scaled.kern(last, id);
+            }
+            glyphs.push((id, x));
+            x += scaled.h_advance(id);
+            last = Some(id);
+        }
+        let size = egui::vec2(x, scaled.ascent() - scaled.descent());
+        let top_left = align.anchor_rect(Rect::from_min_size(pos, size)).min;
+        let baseline = top_left.y + scaled.ascent();
+
+        for (id, x) in glyphs {
+            let glyph = id
+                .with_scale_and_position(scaled.scale(), ab_glyph::point(top_left.x + x, baseline));
+            if let Some(outline) = font.outline_glyph(glyph) {
+                let bounds = outline.px_bounds();
+                let mut coverage = Vec::new();
+                outline.draw(|gx, gy, c| coverage.push((gx, gy, c)));
+                for (gx, gy, c) in coverage {
+                    self.blend(
+                        bounds.min.x as i64 + gx as i64,
+                        bounds.min.y as i64 + gy as i64,
+                        color,
+                        c.min(1.0),
+                    );
+                }
+            }
+        }
+    }
+}
+
+/// An SVG document, drawn shape by shape so the export stays a vector image.
+pub struct Svg {
+    width: u32,
+    height: u32,
+    body: String,
+}
+
+impl Svg {
+    pub fn new(width: u32, height: u32, background: Color32) -> Self {
+        let mut svg = Svg {
+            width,
+            height,
+            body: String::new(),
+        };
+        svg.fill_rect(
+            Rect::from_min_size(Pos2::ZERO, egui::vec2(width as f32, height as f32)),
+            background,
+        );
+        svg
+    }
+
+    /// The complete document.
+    pub fn finish(self) -> String {
+        format!(
+            "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{w}\" height=\"{h}\" \
+             viewBox=\"0 0 {w} {h}\">\n{body}</svg>\n",
+            w = self.width,
+            h = self.height,
+            body = self.body
+        )
+    }
+
+    /// Fill and opacity attributes of `color` under the name `attr`.
+    fn paint(attr: &str, color: Color32) -> String {
+        let [r, g, b, a] = color.to_srgba_unmultiplied();
+        let mut paint = format!("{}=\"#{:02x}{:02x}{:02x}\"", attr, r, g, b);
+        if a != 255 {
+            paint.push_str(&format!(" {}-opacity=\"{:.3}\"", attr, a as f32 / 255.0));
+        }
+        paint
+    }
+}
+
+impl Surface for Svg {
+    fn fill_rect(&mut self, rect: Rect, color: Color32) {
+        self.body.push_str(&format!(
+            "<rect x=\"{:.2}\" y=\"{:.2}\" width=\"{:.2}\" height=\"{:.2}\" {}/>\n",
+            rect.min.x,
+            rect.min.y,
+            rect.width(),
+            rect.height(),
+            Svg::paint("fill", color)
+        ));
+    }
+
+    fn line(&mut self, [a, b]: [Pos2; 2], width: f32, color: Color32) {
+        self.body.push_str(&format!(
+            "<line x1=\"{:.2}\" y1=\"{:.2}\" x2=\"{:.2}\" y2=\"{:.2}\" stroke-width=\"{}\" {}/>\n",
+            a.x,
+            a.y,
+            b.x,
+            b.y,
+            width,
+            Svg::paint("stroke", color)
+        ));
+    }
+
+    fn text(&mut self, pos: Pos2, align: Align2, text: &str, size: f32, color: Color32) {
+        let anchor = match align.x() {
+            egui::Align::Min => "start",
+            egui::Align::Center => "middle",
+            egui::Align::Max => "end",
+        };
+        let baseline = match align.y() {
+            egui::Align::Min => "hanging",
+            egui::Align::Center => "central",
+            egui::Align::Max => "text-after-edge",
+        };
+        let escaped = text
+            .replace('&', "&amp;")
+            .replace('<', "&lt;")
+            .replace('>', "&gt;");
+        self.body.push_str(&format!(
+            "<text x=\"{:.2}\" y=\"{:.2}\" font-family=\"sans-serif\" font-size=\"{}\" \
+             text-anchor=\"{}\" dominant-baseline=\"{}\" {}>{}</text>\n",
+            pos.x,
+            pos.y,
+            size,
+            anchor,
+            baseline,
+            Svg::paint("fill", color),
+            escaped
+        ));
+    }
+}
+
+/// Draws the pixel grid centered in `rect`.
+pub fn draw_pixel_grid(s: &mut impl Surface, rect: Rect, content: &GridContent) {
+    let grid = GridGeometry::new(CHIP_COLUMNS, CHIP_ROWS, rect);
+    for x in 0..CHIP_COLUMNS {
+        for y in 0..CHIP_ROWS {
+            s.fill_rect(grid.pixel_rect(x, y), content.color(x, y));
+        }
+    }
+    for (points, width) in grid.separators() {
+        s.line(points, width, SEPARATOR_COLOR);
+    }
+}
+
+/// Draws column numbers below and row numbers left of `grid`.
+pub fn draw_grid_axes(s: &mut impl Surface, grid: &GridGeometry, text_color: Color32) {
+    for x in (0..grid.columns).step_by(5) {
+        let rect = grid.pixel_rect(x, grid.rows - 1);
+        s.text(
+            rect.center_bottom() + egui::vec2(0.0, 4.0),
+            Align2::CENTER_TOP,
+            &x.to_string(),
+            11.0,
+            text_color,
+        );
+    }
+    for y in (0..grid.rows).step_by(5) {
+        let rect = grid.pixel_rect(0, y);
+        s.text(
+            rect.left_center() - egui::vec2(4.0, 0.0),
+            Align2::RIGHT_CENTER,
+            &y.to_string(),
+            11.0,
+            text_color,
+        );
+    }
+    let bottom = grid.origin.y + grid.size.y;
+    s.text(
+        egui::pos2(grid.origin.x + grid.size.x, bottom + 4.0),
+        Align2::RIGHT_TOP,
+        "x",
+        11.0,
+        text_color,
+    );
+    s.text(
+        egui::pos2(grid.origin.x - 4.0, bottom + 4.0),
+        Align2::RIGHT_TOP,
+        "y",
+        11.0,
+        text_color,
+    );
+}
+
+/// What goes into an exported image besides the chunk itself.
+#[derive(Clone, Debug)]
+pub struct RenderSettings {
+    pub width: u32,
+    pub include_timeline: bool,
+    pub color_mode: ColorMode,
+    pub grouping: LaneGrouping,
+    // Number of chunks shown on the timeline
+    pub timeline_chunks: usize,
+    // None shows the whole frame
+    pub timeline_view: Option<TimeView>,
+    // Shown in the title block
+    pub file_name: Option<String>,
+    // Index in the file of the first chunk passed in, for the title block
+    // when only part of the chunks is rendered from
+    pub first_chunk_idx: usize,
+    // Used by `ColorMode::Calibration`
+    pub calibration: Calibration,
+}
+
+// Height of the title block and room around the grid for its axis labels
+const TITLE_HEIGHT: f32 = 24.0;
+const GRID_MARGIN: f32 = 28.0;
+
+impl RenderSettings {
+    /// The grid keeps its aspect ratio and the timeline goes below it.
+    pub fn height(&self) -> u32 {
+        let timeline = if self.include_timeline {
+            self.width as f32 * 0.4
+        } else {
+            0.0
+        };
+        (self.grid_rect().bottom() + GRID_MARGIN + timeline).round() as u32
+    }
+
+    fn grid_rect(&self) -> Rect {
+        let width = self.width as f32 - 2.0 * GRID_MARGIN;
+        Rect::from_min_size(
+            egui::pos2(GRID_MARGIN, TITLE_HEIGHT),
+            egui::vec2(width, width * CHIP_ROWS as f32 / CHIP_COLUMNS as f32),
+        )
+    }
+}
+
+/// File name, chunk index and frame counter shown above the exported grid.
+pub fn title_block(file_name: Option<&str>, idx: usize, chunk: &Chunk) -> String {
+    let mut title = format!(
+        "chunk {}   frame {}   {} words",
+        idx,
+        chunk.frame_word.frame_counter,
+        chunk.data_words.len()
+    );
+    if let Some(file_name) = file_name {
+        title = format!("{}   {}", file_name, title);
+    }
+    title
+}
+
+/// Draws chunk `idx` the way the window shows it, with a title block on top,
+/// into a surface of `settings.width` x `settings.height()`.
+pub fn draw_view(
+    s: &mut impl Surface,
+    settings: &RenderSettings,
+    chunks: &[Chunk],
+    idx: usize,
+    content: &GridContent,
+) {
+    s.text(
+        egui::pos2(8.0, TITLE_HEIGHT / 2.0),
+        Align2::LEFT_CENTER,
+        &title_block(
+            settings.file_name.as_deref(),
+            settings.first_chunk_idx + idx,
+            &chunks[idx],
+        ),
+        14.0,
+        TEXT_COLOR,
+    );
+
+    let grid_rect = settings.grid_rect();
+    draw_pixel_grid(s, grid_rect, content);
+    draw_grid_axes(
+        s,
+        &GridGeometry::new(CHIP_COLUMNS, CHIP_ROWS, grid_rect),
+        TEXT_COLOR,
+    );
+
+    if settings.include_timeline {
+        let rect = Rect::from_min_max(
+            egui::pos2(0.0, grid_rect.bottom() + GRID_MARGIN),
+            egui::pos2(settings.width as f32, settings.height() as f32),
+        )
+        .shrink(4.0);
+        let mut layout =
+            TimelineLayout::new(0, chunks, idx, settings.timeline_chunks, settings.grouping);
+        let view = settings.timeline_view.unwrap_or_else(|| layout.full_view());
+        timeline::draw_timeline(
+            s,
+            rect,
+            &mut layout,
+            view,
+            chunks,
+            idx,
+            settings.color_mode,
+            &settings.calibration,
+            TEXT_COLOR,
+        );
+    }
+}
+
+/// Renders chunk `idx` to an image, with the hit map computed from the chunk.
+pub fn render_chunk(
+    settings: &RenderSettings,
+    chunks: &[Chunk],
+    idx: usize,
+    afterglow: Option<&Afterglow>,
+) -> RgbaImage {
+    let hit_map = HitMap::from_chunk(&chunks[idx]);
+    let content = GridContent {
+        chunk: Some(&chunks[idx]),
+        hit_map: &hit_map,
+        color_mode: settings.color_mode,
+        calibration: &settings.calibration,
+        time_filter: None,
+        afterglow,
+    };
+    let mut canvas = Canvas::new(settings.width, settings.height(), BACKGROUND);
+    draw_view(&mut canvas, settings, chunks, idx, &content);
+    canvas.image
+}
+
+/// Draws how often each pixel was hit, on the color gradient used for ToT,
+/// with a scale below the grid.
+pub fn draw_hit_counts(s: &mut impl Surface, rect: Rect, counts: &HitCounts) {
+    let scale_height = 30.0;
+    let grid_rect = Rect::from_min_max(
+        rect.min + egui::vec2(GRID_MARGIN, 0.0),
+        rect.max - egui::vec2(GRID_MARGIN, GRID_MARGIN + scale_height),
+    );
+    let grid = GridGeometry::new(CHIP_COLUMNS, CHIP_ROWS, grid_rect);
+    let max = counts.max().max(1);
+    for x in 0..CHIP_COLUMNS {
+        for y in 0..CHIP_ROWS {
+            let color = match counts.get(x, y) {
+                0 => HitType::Other.color(),
+                n => gradient(n as f32 / max as f32),
+            };
+            s.fill_rect(grid.pixel_rect(x, y), color);
+        }
+    }
+    for (points, width) in grid.separators() {
+        s.line(points, width, SEPARATOR_COLOR);
+    }
+    draw_grid_axes(s, &grid, TEXT_COLOR);
+
+    // Gradient from one hit to the most hit pixel
+    let bar = Rect::from_min_size(
+        egui::pos2(grid.origin.x, rect.bottom() - scale_height + 4.0),
+        egui::vec2(grid.size.x, 10.0),
+    );
+    let steps = 64;
+    for i in 0..steps {
+        let f = i as f32 / steps as f32;
+        let left = bar.left() + f * bar.width();
+        s.fill_rect(
+            Rect::from_x_y_ranges(left..=left + bar.width() / steps as f32, bar.y_range()),
+            gradient(f),
+        );
+    }
+    for (pos, align, label) in [
+        (bar.left_bottom(), Align2::LEFT_TOP, "1".to_string()),
+        (bar.center_bottom(), Align2::CENTER_TOP, "hits".to_string()),
+        (bar.right_bottom(), Align2::RIGHT_TOP, max.to_string()),
+    ] {
+        s.text(pos + egui::vec2(0.0, 2.0), align, &label, 11.0, TEXT_COLOR);
+    }
+}
+
+/// Draws `histogram` as bars with a count axis on the left and the values,
+/// formatted by `format_value`, below.
+pub fn draw_histogram(
+    s: &mut impl Surface,
+    rect: Rect,
+    histogram: &Histogram,
+    format_value: impl Fn(f64, f64) -> String,
+) {
+    let plot = Rect::from_min_max(
+        rect.min + egui::vec2(50.0, 8.0),
+        rect.max - egui::vec2(12.0, 20.0),
+    );
+    let axis_color = SEPARATOR_COLOR;
+    s.line([plot.left_bottom(), plot.right_bottom()], 1.0, axis_color);
+    s.line([plot.left_top(), plot.left_bottom()], 1.0, axis_color);
+
+    let max = histogram.max_count().max(1) as f32;
+    let bar_width = plot.width() / histogram.bins.len() as f32;
+    for (i, &count) in histogram.bins.iter().enumerate() {
+        if count == 0 {
+            continue;
+        }
+        let left = plot.left() + i as f32 * bar_width;
+        let top = plot.bottom() - count as f32 / max * plot.height();
+        s.fill_rect(
+            Rect::from_min_max(
+                egui::pos2(left, top),
+                egui::pos2(left + bar_width, plot.bottom()),
+            ),
+            category(0),
+        );
+    }
+
+    // Roughly one labelled tick every 100 px along and 40 px up
+    let span = histogram.max - histogram.min;
+    let step = tick_step(span / (plot.width() / 100.0).max(1.0) as f64);
+    let mut value = (histogram.min / step).ceil() * step;
+    while value <= histogram.max {
+        let x = plot.left() + ((value - histogram.min) / span) as f32 * plot.width();
+        s.line(
+            [
+                egui::pos2(x, plot.bottom()),
+                egui::pos2(x, plot.bottom() + 4.0),
+            ],
+            1.0,
+            axis_color,
+        );
+        s.text(
+            egui::pos2(x, rect.bottom()),
+            Align2::CENTER_BOTTOM,
+            &format_value(value, step),
+            11.0,
+            TEXT_COLOR,
+        );
+        value += step;
+    }
+    let step = tick_step(max as f64 / (plot.height() / 40.0).max(1.0) as f64).max(1.0);
+    let mut count = 0.0;
+    while count <= max as f64 {
+        let y = plot.bottom() - (count / max as f64) as f32 * plot.height();
+        s.line(
+            [egui::pos2(plot.left() - 4.0, y), egui::pos2(plot.left(), y)],
+            1.0,
+            axis_color,
+        );
+        s.text(
+            egui::pos2(plot.left() - 6.0, y),
+            Align2::RIGHT_CENTER,
+            &format!("{:.0}", count),
+            11.0,
+            TEXT_COLOR,
+        );
+        count += step;
+    }
+}
+
+/// Draws `title` in a title block above whatever `draw` puts below it, into
+/// an image `width` wide with `height` left below the title.
+pub fn render_titled(
+    width: u32,
+    height: u32,
+    title: &str,
+    draw: impl FnOnce(&mut Canvas, Rect),
+) -> RgbaImage {
+    let mut canvas = Canvas::new(width, height + TITLE_HEIGHT as u32, BACKGROUND);
+    canvas.text(
+        egui::pos2(8.0, TITLE_HEIGHT / 2.0),
+        Align2::LEFT_CENTER,
+        title,
+        14.0,
+        TEXT_COLOR,
+    );
+    let rect = Rect::from_min_max(egui::pos2(0.0, TITLE_HEIGHT), canvas.rect().max).shrink(4.0);
+    draw(&mut canvas, rect);
+    canvas.image
+}
+
+/// Renders the same as `render_titled` without the title, as an svg document.
+pub fn render_svg(width: u32, height: u32, draw: impl FnOnce(&mut Svg, Rect)) -> String {
+    let mut svg = Svg::new(width, height, BACKGROUND);
+    let rect = Rect::from_min_size(Pos2::ZERO, egui::vec2(width as f32, height as f32));
+    draw(&mut svg, rect.shrink(4.0));
+    svg.finish()
+}
+
+/// Height needed below the title to show hit counts `width` wide.
+pub fn hit_counts_height(width: u32) -> u32 {
+    let grid = (width as f32 - 2.0 * GRID_MARGIN) * CHIP_ROWS as f32 / CHIP_COLUMNS as f32;
+    (grid + GRID_MARGIN + 30.0 + 8.0).round() as u32
+}
@@ -0,0 +1,148 @@
+use std::io::Write;
+use std::net::{TcpStream, UdpSocket};
+use std::time::{Duration, Instant};
+
+use crate::net::{encode_chunk, Protocol, WireFormat};
+use crate::tdcpix::{frame_offset, Chunk, FRAME_PERIOD_PS};
+
+// Pauses in the capture, or jumps of the frame counter, are shortened to a second
+const MAX_GAP_PS: u64 = 1_000_000_000_000;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Pace {
+    // Spaced like the original run, using the frame counters
+    RealTime,
+    WordsPerSecond(f64),
+    AsFastAsPossible,
+}
+
+pub struct ReplayConfig {
+    pub protocol: Protocol,
+    pub format: WireFormat,
+    // host:port of the receiver
+    pub address: String,
+    pub pace: Pace,
+    pub repeat: bool,
+}
+
+enum Sender {
+    Tcp(TcpStream),
+    Udp(UdpSocket),
+}
+
+impl Sender {
+    fn send(&mut self, bytes: &[u8]) -> std::io::Result<()> {
+        match self {
+            Sender::Tcp(stream) => stream.write_all(bytes),
+            // One datagram per chunk
+            Sender::Udp(socket) => socket.send(bytes).map(|_| ()),
+        }
+    }
+}
+
+/// Sends the chunks to a receiver, returns the number of words sent.
+pub fn replay(chunks: &[Chunk], config: &ReplayConfig) -> std::io::Result<u64> {
+    let mut sender = match config.protocol {
+        Protocol::Tcp => Sender::Tcp(TcpStream::connect(&config.address)?),
+        Protocol::Udp => {
+            let socket = UdpSocket::bind("0.0.0.0:0")?;
+            socket.connect(&config.address)?;
+            Sender::Udp(socket)
+        }
+    };
+
+    let mut words_sent = 0;
+    loop {
+        let start = Instant::now();
+        let mut clock = RealTimeClock::default();
+        let mut words_this_pass = 0u64;
+
+        for chunk in chunks {
+            let due_ps = match config.pace {
+                Pace::RealTime => Some(clock.due_ps(chunk.frame_word.frame_counter)),
+                Pace::WordsPerSecond(rate) => Some((words_this_pass as f64 / rate * 1e12) as u64),
+                Pace::AsFastAsPossible => None,
+            };
+            if let Some(due_ps) = due_ps {
+                wait_until(start + Duration::from_nanos(due_ps / 1000));
+            }
+
+            sender.send(&encode_chunk(chunk, config.format))?;
+            words_this_pass += chunk.data_words.len() as u64 + 1;
+        }
+
+        words_sent += words_this_pass;
+        if !config.repeat || chunks.is_empty() {
+            return Ok(words_sent);
+        }
+    }
+}
+
+/// When chunks are due after the start of a pass, going by the frames passed
+/// between consecutive chunks. Chunks from before the previous one, out of
+/// order, are sent right away.
+#[derive(Default)]
+struct RealTimeClock {
+    previous_frame: Option<u32>,
+    due_ps: u64,
+}
+
+impl RealTimeClock {
+    fn due_ps(&mut self, frame_counter: u32) -> u64 {
+        if let Some(previous) = self.previous_frame {
+            let frames = frame_offset(frame_counter, previous).max(0) as u64;
+            self.due_ps += (frames * FRAME_PERIOD_PS).min(MAX_GAP_PS);
+        }
+        self.previous_frame = Some(frame_counter);
+        self.due_ps
+    }
+}
+
+fn wait_until(due: Instant) {
+    // Sleeping is too coarse for single frames, so only sleep once we are
+    // at least a millisecond ahead
+    let now = Instant::now();
+    if due > now + Duration::from_millis(1) {
+        std::thread::sleep(due - now);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn due_times(frame_counters: &[u32]) -> Vec<u64> {
+        let mut clock = RealTimeClock::default();
+        frame_counters.iter().map(|&fc| clock.due_ps(fc)).collect()
+    }
+
+    #[test]
+    fn real_time_delay_counts_frames_since_the_first_chunk() {
+        assert_eq!(
+            due_times(&[100, 101, 103]),
+            [0, FRAME_PERIOD_PS, 3 * FRAME_PERIOD_PS]
+        );
+    }
+
+    #[test]
+    fn real_time_delay_continues_across_counter_wraps() {
+        // The frame counter is 28 bits wide, the pass goes past its middle and
+        // the wrap, which an offset from the first chunk could not follow
+        let step = 100_000;
+        let frame_counters: Vec<u32> = (0..3000).map(|i| (i * step) & ((1 << 28) - 1)).collect();
+        let times = due_times(&frame_counters);
+        for (i, &t) in times.iter().enumerate() {
+            assert_eq!(t, i as u64 * step as u64 * FRAME_PERIOD_PS);
+        }
+    }
+
+    #[test]
+    fn real_time_delay_sends_earlier_frames_right_away() {
+        assert_eq!(due_times(&[100, 98, 99]), [0, 0, FRAME_PERIOD_PS]);
+    }
+
+    #[test]
+    fn real_time_delay_shortens_long_gaps() {
+        assert_eq!(due_times(&[0, 100_000_000, 100_000_001])[1], MAX_GAP_PS);
+    }
+}
@@ -0,0 +1,283 @@
+use std::collections::BTreeMap;
+
+use crate::hitmap::HitCounts;
+use crate::render::{draw_histogram, draw_hit_counts, hit_counts_height, render_svg};
+use crate::stats::{
+    dead_pixels, frames_covered, noisy_pixels, rate_hz, start_time_histogram, tot_histogram,
+    Histogram,
+};
+use crate::tdcpix::{Chunk, FRAME_PERIOD_PS, MAPPED_COLUMNS};
+use crate::validation::{summarize_chunks, Issue};
+use crate::widgets::timeline::{format_time, format_time_precise};
+
+pub struct ReportConfig {
+    // A pixel is noisy when hit more than this many times the average
+    pub noisy_factor: f64,
+    pub bins: usize,
+    // Width of the plots in pixels
+    pub width: u32,
+    // Issues listed one by one, the rest are only counted
+    pub max_listed_issues: usize,
+}
+
+const STYLE: &str = "body { font-family: sans-serif; margin: 2em; max-width: 60em; }
+table { border-collapse: collapse; margin: 0.5em 0; }
+th, td { border: 1px solid #ccc; padding: 0.2em 0.6em; text-align: left; }
+td.num { text-align: right; font-family: monospace; }
+.warn { color: #b35c00; }
+.ok { color: #2a7d2a; }";
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// A two column table of labels and values.
+fn key_value_table(rows: &[(&str, String)]) -> String {
+    let mut html = String::from("<table>\n");
+    for (key, value) in rows {
+        html.push_str(&format!(
+            "<tr><th>{}</th><td class=\"num\">{}</td></tr>\n",
+            key,
+            escape(value)
+        ));
+    }
+    html.push_str("</table>\n");
+    html
+}
+
+fn histogram_section(title: &str, histogram: &Histogram, width: u32) -> String {
+    let svg = render_svg(width, width / 2, |svg, rect| {
+        draw_histogram(svg, rect, histogram, format_time)
+    });
+    format!(
+        "<h3>{}</h3>\n{}<p>{} entries, bins of {}, {} below and {} above the range.</p>\n",
+        title,
+        svg,
+        histogram.entries(),
+        format_time_precise(histogram.bin_width()),
+        histogram.underflow,
+        histogram.overflow
+    )
+}
+
+/// Builds a self-contained html page with statistics of `chunks`, read from
+/// the file called `name`. The plots are embedded as svg.
+pub fn build_report(name: &str, chunks: &[Chunk], config: &ReportConfig) -> String {
+    let summaries = summarize_chunks(chunks);
+    let counts = HitCounts::from_chunks(chunks);
+    let words: usize = summaries.iter().map(|s| s.words).sum();
+
+    let mut html = format!(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n\
+         <title>TDCpix report: {name}</title>\n<style>\n{STYLE}\n</style>\n</head>\n<body>\n\
+         <h1>TDCpix report: {name}</h1>\n",
+        name = escape(name)
+    );
+
+    // Summary
+    let frames = match (chunks.first(), chunks.last()) {
+        (Some(first), Some(last)) => format!(
+            "{} to {}",
+            first.frame_word.frame_counter, last.frame_word.frame_counter
+        ),
+        _ => "-".to_string(),
+    };
+    let frames_covered = frames_covered(chunks);
+    let duration_ms = frames_covered as f64 * FRAME_PERIOD_PS as f64 * 1e-9;
+    html.push_str("<h2>Summary</h2>\n");
+    html.push_str(&key_value_table(&[
+        ("File", name.to_string()),
+        ("Chunks", chunks.len().to_string()),
+        ("Frame counters", frames),
+        ("Time covered", format!("{:.3} ms", duration_ms)),
+        ("Data words", words.to_string()),
+        (
+            "Word rate",
+            format!("{:.3} kHz", rate_hz(words as u64, frames_covered) / 1e3),
+        ),
+        (
+            "Words / chunk",
+            format!("{:.3}", words as f64 / chunks.len().max(1) as f64),
+        ),
+        (
+            "Empty chunks",
+            summaries
+                .iter()
+                .filter(|s| s.words == 0)
+                .count()
+                .to_string(),
+        ),
+        (
+            "Words with pileup",
+            summaries
+                .iter()
+                .map(|s| s.pileup_words)
+                .sum::<usize>()
+                .to_string(),
+        ),
+    ]));
+
+    // Validation
+    let issues: Vec<(usize, u32, &Issue)> = summaries
+        .iter()
+        .enumerate()
+        .flat_map(|(i, s)| {
+            s.issues
+                .iter()
+                .map(move |issue| (i, s.frame_counter, issue))
+        })
+        .collect();
+    html.push_str("<h2>Validation</h2>\n");
+    if issues.is_empty() {
+        html.push_str("<p class=\"ok\">All chunks passed validation.</p>\n");
+    } else {
+        let failed = summaries.iter().filter(|s| !s.issues.is_empty()).count();
+        html.push_str(&format!(
+            "<p class=\"warn\">{} issues in {} of {} chunks.</p>\n",
+            issues.len(),
+            failed,
+            chunks.len()
+        ));
+        let mut kinds: BTreeMap<&str, usize> = BTreeMap::new();
+        for (_, _, issue) in &issues {
+            *kinds.entry(issue.kind()).or_default() += 1;
+        }
+        let kinds: Vec<(&str, String)> = kinds
+            .into_iter()
+            .map(|(kind, n)| (kind, n.to_string()))
+            .collect();
+        html.push_str(&key_value_table(&kinds));
+
+        html.push_str("<table>\n<tr><th>Chunk</th><th>Frame</th><th>Issue</th></tr>\n");
+        for (chunk_idx, frame_counter, issue) in issues.iter().take(config.max_listed_issues) {
+            html.push_str(&format!(
+                "<tr><td class=\"num\">{}</td><td class=\"num\">{}</td><td>{}</td></tr>\n",
+                chunk_idx,
+                frame_counter,
+                escape(&issue.to_string())
+            ));
+        }
+        html.push_str("</table>\n");
+        if issues.len() > config.max_listed_issues {
+            html.push_str(&format!(
+                "<p>{} more issues are not listed.</p>\n",
+                issues.len() - config.max_listed_issues
+            ));
+        }
+    }
+
+    // Hitmap and pixels
+    html.push_str("<h2>Hitmap</h2>\n");
+    html.push_str(&render_svg(
+        config.width,
+        hit_counts_height(config.width),
+        |svg, rect| draw_hit_counts(svg, rect, &counts),
+    ));
+    html.push_str(&format!(
+        "<p>Only the first {} columns are mapped, the pixel lists cover those.</p>\n",
+        MAPPED_COLUMNS
+    ));
+
+    let dead = dead_pixels(chunks);
+    html.push_str(&format!("<h3>Dead pixels ({})</h3>\n<p>", dead.len()));
+    if dead.is_empty() {
+        html.push_str("None");
+    } else {
+        let dead: Vec<String> = dead
+            .iter()
+            .map(|(x, y)| format!("({}, {})", x, y))
+            .collect();
+        html.push_str(&dead.join(", "));
+    }
+    html.push_str("</p>\n");
+
+    let noisy = noisy_pixels(&counts, config.noisy_factor);
+    html.push_str(&format!(
+        "<h3>Noisy pixels ({})</h3>\n<p>Hit more than {} times as often as the average pixel.</p>\n",
+        noisy.len(),
+        config.noisy_factor
+    ));
+    if !noisy.is_empty() {
+        html.push_str("<table>\n<tr><th>Pixel</th><th>Hits</th><th>Rate</th></tr>\n");
+        for ((x, y), hits) in &noisy {
+            html.push_str(&format!(
+                "<tr><td>({}, {})</td><td class=\"num\">{}</td><td class=\"num\">{:.3} kHz</td></tr>\n",
+                x,
+                y,
+                hits,
+                rate_hz(*hits as u64, frames_covered) / 1e3
+            ));
+        }
+        html.push_str("</table>\n");
+    }
+
+    // Histograms
+    html.push_str("<h2>Histograms</h2>\n");
+    html.push_str(&histogram_section(
+        "Leading time",
+        &start_time_histogram(chunks, config.bins),
+        config.width,
+    ));
+    html.push_str(&histogram_section(
+        "Time over threshold",
+        &tot_histogram(chunks, config.bins),
+        config.width,
+    ));
+
+    html.push_str("</body>\n</html>\n");
+    html
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tdcpix::DataWord;
+
+    fn config() -> ReportConfig {
+        ReportConfig {
+            noisy_factor: 5.0,
+            bins: 8,
+            width: 200,
+            max_listed_issues: 1,
+        }
+    }
+
+    fn test_chunks(frame_counters: &[u32]) -> Vec<Chunk> {
+        frame_counters
+            .iter()
+            .map(|&fc| Chunk::new(vec![DataWord::for_pixel(1, 2, 1000, 500)], fc))
+            .collect()
+    }
+
+    #[test]
+    fn summary_and_clean_validation() {
+        let html = build_report("run <1>", &test_chunks(&[5, 6, 7]), &config());
+        assert!(html.contains("TDCpix report: run &lt;1&gt;"));
+        assert!(html.contains("<tr><th>Chunks</th><td class=\"num\">3</td></tr>"));
+        assert!(html.contains("<tr><th>Frame counters</th><td class=\"num\">5 to 7</td></tr>"));
+        assert!(html.contains("All chunks passed validation."));
+        assert!(html.contains("<h3>Dead pixels (449)</h3>"));
+        assert!(html.ends_with("</html>\n"));
+    }
+
+    #[test]
+    fn time_covered_includes_frame_gaps() {
+        let html = build_report("gaps", &test_chunks(&[0, 999]), &config());
+        let ms = 1000.0 * FRAME_PERIOD_PS as f64 * 1e-9;
+        assert!(html.contains(&format!(
+            "<tr><th>Time covered</th><td class=\"num\">{:.3} ms</td></tr>",
+            ms
+        )));
+    }
+
+    #[test]
+    fn issues_beyond_the_limit_are_only_counted() {
+        let html = build_report("gaps", &test_chunks(&[0, 2, 4]), &config());
+        assert!(html.contains("2 issues in 2 of 3 chunks."));
+        assert!(html.contains("<tr><th>frame gap</th><td class=\"num\">2</td></tr>"));
+        assert!(html.contains("1 more issues are not listed."));
+    }
+}
@@ -0,0 +1,316 @@
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use crate::tdcpix::{
+    pixel_to_address, Chunk, DataWord, COARSE_TIME_PS, FRAME_PERIOD_PS, HA_PR_COL, MAPPED_COLUMNS,
+    PIXELS_PR_ARBITER,
+};
+
+/// A particle crossing as it really happened, before the chip got to it.
+#[derive(Clone, Copy, Debug)]
+pub struct TrueHit {
+    pub x: u8,
+    pub y: u8,
+    // Time within the frame in ps
+    pub time: u64,
+    // Deposited charge in fC
+    pub charge: f32,
+}
+
+#[derive(Clone, Debug)]
+pub struct SimulationConfig {
+    // Hits below the discriminator threshold never make it into a word
+    pub threshold_fc: f32,
+    // Time over threshold per fC above threshold
+    pub tot_ps_per_fc: f32,
+    // Hits in the same arbiter closer than this share a word
+    pub simultaneity_ps: u64,
+}
+
+impl Default for SimulationConfig {
+    fn default() -> Self {
+        SimulationConfig {
+            threshold_fc: 0.7,
+            tot_ps_per_fc: 10_000.0,
+            simultaneity_ps: COARSE_TIME_PS,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum HitFate {
+    // Hit is the leading hit or shares the arbiter of the word
+    Word(usize),
+    // Hit arrived while the arbiter was busy and only set a pileup flag
+    Pileup(usize),
+    BelowThreshold,
+    // Pixel outside the mapped quarterchip
+    Unmapped,
+    // Time not within the frame, the leading time fields cannot hold it
+    OutOfFrame,
+}
+
+pub struct SimulatedFrame {
+    pub chunk: Chunk,
+    // What happened to each true hit, same order as the input
+    pub fates: Vec<HitFate>,
+}
+
+struct OpenWord {
+    start_time: u64,
+    busy_until: u64,
+    arbiter: u8,
+    pileup: u8,
+    hits: Vec<usize>,
+    pileup_hits: Vec<usize>,
+}
+
+/// Emulates what the hit arbiters of the chip would output for one frame of true hits.
+///
+/// Each arbiter serves 5 pixels. The first hit opens a word and keeps the arbiter
+/// busy for its ToT. Hits within the simultaneity window are merged into the arbiter
+/// pattern and keep it busy until the last of them is over, later hits during the
+/// busy time only set their pileup bit.
+pub fn simulate_frame(
+    hits: &[TrueHit],
+    frame_counter: u32,
+    config: &SimulationConfig,
+) -> SimulatedFrame {
+    let mut fates = vec![HitFate::Unmapped; hits.len()];
+
+    // Hits grouped by arbiter address, each group sorted by time
+    let mut groups: BTreeMap<u8, Vec<usize>> = BTreeMap::new();
+    for (i, hit) in hits.iter().enumerate() {
+        if hit.x >= MAPPED_COLUMNS || hit.y >= HA_PR_COL * PIXELS_PR_ARBITER {
+            continue;
+        }
+        if hit.time >= FRAME_PERIOD_PS {
+            fates[i] = HitFate::OutOfFrame;
+            continue;
+        }
+        if hit.charge < config.threshold_fc {
+            fates[i] = HitFate::BelowThreshold;
+            continue;
+        }
+        groups
+            .entry(pixel_to_address(hit.x, hit.y).0)
+            .or_default()
+            .push(i);
+    }
+
+    let mut words: Vec<OpenWord> = Vec::new();
+    for mut group in groups.into_values() {
+        group.sort_by_key(|&i| hits[i].time);
+        let mut open: Option<OpenWord> = None;
+        for i in group {
+            let hit = &hits[i];
+            let arbiter_bit = pixel_to_address(hit.x, hit.y).1;
+            let end = hit.time.saturating_add(tot_of(hit.charge, config));
+            match &mut open {
+                Some(w) if hit.time < w.start_time + config.simultaneity_ps => {
+                    w.arbiter |= arbiter_bit;
+                    w.busy_until = w.busy_until.max(end);
+                    w.hits.push(i);
+                }
+                Some(w) if hit.time < w.busy_until => {
+                    w.pileup |= arbiter_bit;
+                    w.pileup_hits.push(i);
+                }
+                _ => {
+                    if let Some(w) = open.take() {
+                        words.push(w);
+                    }
+                    open = Some(OpenWord {
+                        start_time: hit.time,
+                        busy_until: end,
+                        arbiter: arbiter_bit,
+                        pileup: 0,
+                        hits: vec![i],
+                        pileup_hits: Vec::new(),
+                    });
+                }
+            }
+        }
+        if let Some(w) = open {
+            words.push(w);
+        }
+    }
+
+    // The chip reads words out in time order
+    words.sort_by_key(|w| w.start_time);
+
+    let mut data_words = Vec::with_capacity(words.len());
+    for (word_idx, w) in words.into_iter().enumerate() {
+        let first = &hits[w.hits[0]];
+        let tot = w.busy_until - w.start_time;
+        let mut dw = DataWord::for_pixel(first.x, first.y, w.start_time, tot);
+        dw.address_arbiter = w.arbiter;
+        dw.address_pileup = w.pileup;
+        dw.raw = dw.encode();
+        data_words.push(dw);
+
+        for i in w.hits {
+            fates[i] = HitFate::Word(word_idx);
+        }
+        for i in w.pileup_hits {
+            fates[i] = HitFate::Pileup(word_idx);
+        }
+    }
+
+    SimulatedFrame {
+        chunk: Chunk::new(data_words, frame_counter),
+        fates,
+    }
+}
+
+fn tot_of(charge: f32, config: &SimulationConfig) -> u64 {
+    ((charge - config.threshold_fc).max(0.0) * config.tot_ps_per_fc) as u64
+}
+
+/// Reads true hits from a csv file with the columns frame,x,y,time_ps,charge_fc.
+/// Empty lines and lines starting with '#' are skipped, the first line may be
+/// a header.
+pub fn parse_true_hits_csv(file: &Path) -> Result<BTreeMap<u32, Vec<TrueHit>>, String> {
+    let content =
+        std::fs::read_to_string(file).map_err(|e| format!("{}: {}", file.display(), e))?;
+    parse_true_hits(&content).map_err(|e| format!("{}:{}", file.display(), e))
+}
+
+// Errors start with the line number, `parse_true_hits_csv` puts the file name in front
+fn parse_true_hits(content: &str) -> Result<BTreeMap<u32, Vec<TrueHit>>, String> {
+    let mut frames: BTreeMap<u32, Vec<TrueHit>> = BTreeMap::new();
+    let mut first_line = true;
+    for (line_nr, line) in content.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let err = |e: String| format!("{}: {}", line_nr + 1, e);
+        let cols: Vec<&str> = line.split(',').map(|c| c.trim()).collect();
+        if cols.len() != 5 {
+            return Err(err(format!("expected 5 columns, got {}", cols.len())));
+        }
+        // A header names the columns instead of starting with a frame number
+        if std::mem::take(&mut first_line) && cols[0].parse::<u32>().is_err() {
+            continue;
+        }
+        let frame: u32 = parse_col(cols[0]).map_err(err)?;
+        let hit = TrueHit {
+            x: parse_col(cols[1]).map_err(err)?,
+            y: parse_col(cols[2]).map_err(err)?,
+            time: parse_col(cols[3]).map_err(err)?,
+            charge: parse_col(cols[4]).map_err(err)?,
+        };
+        frames.entry(frame).or_default().push(hit);
+    }
+    Ok(frames)
+}
+
+fn parse_col<T: std::str::FromStr>(col: &str) -> Result<T, String>
+where
+    T::Err: std::fmt::Display,
+{
+    col.parse::<T>().map_err(|e| format!("'{}': {}", col, e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hit(x: u8, y: u8, time: u64, charge: f32) -> TrueHit {
+        TrueHit { x, y, time, charge }
+    }
+
+    // 1 fC above threshold is 10 ns of ToT
+    fn config() -> SimulationConfig {
+        SimulationConfig {
+            threshold_fc: 1.0,
+            tot_ps_per_fc: 10_000.0,
+            simultaneity_ps: COARSE_TIME_PS,
+        }
+    }
+
+    #[test]
+    fn hits_are_grouped_by_arbiter() {
+        // (0, 0) and (0, 9) share an arbiter, (0, 1) has its own
+        let hits = [
+            hit(0, 0, 100_000, 2.0),
+            hit(0, 1, 100_000, 2.0),
+            hit(0, 9, 500_000, 2.0),
+        ];
+        let frame = simulate_frame(&hits, 3, &config());
+        assert_eq!(frame.chunk.data_words.len(), 3);
+        assert_eq!(
+            frame.fates,
+            [HitFate::Word(0), HitFate::Word(1), HitFate::Word(2)]
+        );
+        assert_eq!(frame.chunk.frame_word.frame_counter, 3);
+    }
+
+    #[test]
+    fn simultaneous_hits_share_a_word_and_its_busy_time() {
+        // The second hit starts a little later and ends well after the first
+        let hits = [hit(0, 0, 100_000, 2.0), hit(0, 9, 108_000, 3.0)];
+        let config = SimulationConfig {
+            simultaneity_ps: 10_000,
+            ..config()
+        };
+        let frame = simulate_frame(&hits, 0, &config);
+        assert_eq!(frame.fates, [HitFate::Word(0), HitFate::Word(0)]);
+        let dw = &frame.chunk.data_words[0];
+        assert_eq!(dw.address_arbiter, 0b11);
+        assert_eq!(dw.hit_pixels(), [(0, 0), (0, 9)]);
+        // Busy from the first start to the end of the second hit, 28 ns
+        let tot = dw.get_duration() as i64;
+        assert!((tot - 28_000).abs() < 200, "{}", tot);
+    }
+
+    #[test]
+    fn hits_during_the_busy_time_set_pileup() {
+        let hits = [
+            hit(0, 0, 100_000, 2.0),
+            hit(0, 18, 105_000, 2.0),
+            hit(0, 9, 200_000, 2.0),
+        ];
+        let frame = simulate_frame(&hits, 0, &config());
+        assert_eq!(
+            frame.fates,
+            [HitFate::Word(0), HitFate::Pileup(0), HitFate::Word(1)]
+        );
+        assert_eq!(frame.chunk.data_words[0].address_pileup, 0b100);
+        assert_eq!(frame.chunk.data_words[1].address_pileup, 0);
+    }
+
+    #[test]
+    fn hits_that_cannot_be_encoded_are_left_out() {
+        let hits = [
+            hit(0, 0, FRAME_PERIOD_PS, 2.0),
+            hit(0, 0, u64::MAX, 2.0),
+            hit(MAPPED_COLUMNS, 0, 0, 2.0),
+            hit(0, 0, 0, 0.5),
+            hit(0, 1, 0, f32::MAX),
+        ];
+        let frame = simulate_frame(&hits, 0, &config());
+        assert_eq!(
+            frame.fates,
+            [
+                HitFate::OutOfFrame,
+                HitFate::OutOfFrame,
+                HitFate::Unmapped,
+                HitFate::BelowThreshold,
+                HitFate::Word(0)
+            ]
+        );
+    }
+
+    #[test]
+    fn csv_may_start_with_a_header() {
+        let frames =
+            parse_true_hits("frame,x,y,time_ps,charge_fc\n# run 1\n4,1,2,300,1.5\n").unwrap();
+        assert_eq!(frames[&4].len(), 1);
+        assert_eq!(frames[&4][0].time, 300);
+
+        let err = parse_true_hits("4,1,2,300,1.5\nframe,x,y,time_ps,charge_fc\n").unwrap_err();
+        assert!(err.starts_with("2: 'frame'"), "{}", err);
+    }
+}
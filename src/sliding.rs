@@ -0,0 +1,95 @@
+use std::ops::Deref;
+
+/// A `Vec` that live sources drop old items from the front of. Dropping only
+/// moves the start, the storage is compacted once the dropped part is as large
+/// as the kept one, so trimming to a capacity on every poll stays cheap.
+#[derive(Clone, Debug)]
+pub struct SlidingVec<T> {
+    items: Vec<T>,
+    start: usize,
+}
+
+impl<T> SlidingVec<T> {
+    pub fn new() -> Self {
+        SlidingVec {
+            items: Vec::new(),
+            start: 0,
+        }
+    }
+
+    pub fn push(&mut self, item: T) {
+        self.items.push(item);
+    }
+
+    pub fn clear(&mut self) {
+        self.items.clear();
+        self.start = 0;
+    }
+
+    /// Drops the first `n` items, or all of them if there are fewer.
+    pub fn drop_front(&mut self, n: usize) {
+        self.start += n.min(self.len());
+        if self.start >= self.items.len() - self.start {
+            self.items.drain(..self.start);
+            self.start = 0;
+        }
+    }
+}
+
+impl<T> Default for SlidingVec<T> {
+    fn default() -> Self {
+        SlidingVec::new()
+    }
+}
+
+impl<T> Deref for SlidingVec<T> {
+    type Target = [T];
+
+    fn deref(&self) -> &[T] {
+        &self.items[self.start..]
+    }
+}
+
+impl<T> From<Vec<T>> for SlidingVec<T> {
+    fn from(items: Vec<T>) -> Self {
+        SlidingVec { items, start: 0 }
+    }
+}
+
+impl<T> Extend<T> for SlidingVec<T> {
+    fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
+        self.items.extend(iter);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dropped_items_are_gone() {
+        let mut v = SlidingVec::from((0..10).collect::<Vec<_>>());
+        v.drop_front(3);
+        assert_eq!(&v[..], &[3, 4, 5, 6, 7, 8, 9]);
+        v.extend([10, 11]);
+        v.drop_front(2);
+        assert_eq!(v.len(), 7);
+        assert_eq!(v[0], 5);
+        assert_eq!(v.last(), Some(&11));
+        v.drop_front(100);
+        assert!(v.is_empty());
+    }
+
+    #[test]
+    fn storage_is_compacted() {
+        let mut v = SlidingVec::new();
+        for i in 0..1000 {
+            v.push(i);
+            if v.len() > 10 {
+                v.drop_front(1);
+            }
+            assert!(v.items.len() <= 2 * 10);
+        }
+        assert_eq!(&v[..], &(990..1000).collect::<Vec<_>>()[..]);
+    }
+}
@@ -0,0 +1,198 @@
+use std::collections::HashSet;
+
+use crate::hitmap::{HitCounts, CHIP_ROWS};
+use crate::tdcpix::{
+    frame_offset, Chunk, DataWord, FRAME_PERIOD_PS, MAPPED_COLUMNS, MAX_DURATION_PS,
+    MAX_START_TIME_PS,
+};
+
+/// Counts of values in equally wide bins from `min` up to `max`.
+#[derive(Clone, Debug)]
+pub struct Histogram {
+    pub min: f64,
+    pub max: f64,
+    pub bins: Vec<u32>,
+    // Values below `min` and from `max` on
+    pub underflow: u32,
+    pub overflow: u32,
+}
+
+impl Histogram {
+    pub fn new(min: f64, max: f64, bins: usize) -> Self {
+        Histogram {
+            min,
+            max,
+            bins: vec![0; bins.max(1)],
+            underflow: 0,
+            overflow: 0,
+        }
+    }
+
+    pub fn fill(&mut self, value: f64) {
+        if value < self.min {
+            self.underflow += 1;
+        } else if value >= self.max {
+            self.overflow += 1;
+        } else {
+            // Rounding can put a value just below `max` one bin too far
+            let last = self.bins.len() - 1;
+            let bin = ((value - self.min) / self.bin_width()) as usize;
+            self.bins[bin.min(last)] += 1;
+        }
+    }
+
+    pub fn bin_width(&self) -> f64 {
+        (self.max - self.min) / self.bins.len() as f64
+    }
+
+    pub fn max_count(&self) -> u32 {
+        self.bins.iter().copied().max().unwrap_or(0)
+    }
+
+    pub fn entries(&self) -> u64 {
+        self.bins.iter().map(|&c| c as u64).sum::<u64>()
+            + self.underflow as u64
+            + self.overflow as u64
+    }
+}
+
+/// Histogram of `value` of every data word, over the whole range the value can take.
+fn word_histogram(
+    chunks: &[Chunk],
+    bins: usize,
+    max: u64,
+    value: impl Fn(&DataWord) -> u64,
+) -> Histogram {
+    let mut histogram = Histogram::new(0.0, max as f64, bins);
+    for dw in chunks.iter().flat_map(|c| &c.data_words) {
+        histogram.fill(value(dw) as f64);
+    }
+    histogram
+}
+
+/// Leading times in ps relative to the frame.
+pub fn start_time_histogram(chunks: &[Chunk], bins: usize) -> Histogram {
+    word_histogram(chunks, bins, MAX_START_TIME_PS, DataWord::get_start_time)
+}
+
+/// Time over threshold in ps.
+pub fn tot_histogram(chunks: &[Chunk], bins: usize) -> Histogram {
+    word_histogram(chunks, bins, MAX_DURATION_PS, DataWord::get_duration)
+}
+
+/// Pixels of the mapped quarterchip that were never hit, by any bit of the
+/// arbiter patterns.
+pub fn dead_pixels(chunks: &[Chunk]) -> Vec<(u8, u8)> {
+    let hit: HashSet<(u8, u8)> = chunks
+        .iter()
+        .flat_map(|c| &c.data_words)
+        .flat_map(|dw| dw.hit_pixels())
+        .collect();
+    mapped_pixels().filter(|p| !hit.contains(p)).collect()
+}
+
+/// Pixels of the mapped quarterchip hit more than `factor` times as often as
+/// the mapped pixels are on average, with their count, most hit first.
+pub fn noisy_pixels(counts: &HitCounts, factor: f64) -> Vec<((u8, u8), u32)> {
+    let pixels: Vec<(u8, u8)> = mapped_pixels().collect();
+    let total: u64 = pixels.iter().map(|&(x, y)| counts.get(x, y) as u64).sum();
+    let mean = total as f64 / pixels.len().max(1) as f64;
+    let mut noisy: Vec<((u8, u8), u32)> = pixels
+        .into_iter()
+        .map(|(x, y)| ((x, y), counts.get(x, y)))
+        .filter(|&(_, count)| count as f64 > factor * mean)
+        .collect();
+    noisy.sort_by_key(|&(_, count)| std::cmp::Reverse(count));
+    noisy
+}
+
+fn mapped_pixels() -> impl Iterator<Item = (u8, u8)> {
+    (0..MAPPED_COLUMNS).flat_map(|x| (0..CHIP_ROWS).map(move |y| (x, y)))
+}
+
+/// Frames from the first to the last chunk, following the frame counter
+/// across gaps and wraps. Chunks out of order add nothing.
+pub fn frames_covered(chunks: &[Chunk]) -> u64 {
+    if chunks.is_empty() {
+        return 0;
+    }
+    let steps: u64 = chunks
+        .windows(2)
+        .map(|w| frame_offset(w[1].frame_word.frame_counter, w[0].frame_word.frame_counter))
+        .map(|frames| frames.max(0) as u64)
+        .sum();
+    steps + 1
+}
+
+/// Rate in Hz of `count` words over `frames` frames.
+pub fn rate_hz(count: u64, frames: u64) -> f64 {
+    let seconds = frames as f64 * FRAME_PERIOD_PS as f64 * 1e-12;
+    if seconds > 0.0 {
+        count as f64 / seconds
+    } else {
+        0.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn histogram_edges() {
+        let mut histogram = Histogram::new(0.0, 10.0, 5);
+        for value in [-1.0, 0.0, 1.9, 2.0, 9.99, 10.0] {
+            histogram.fill(value);
+        }
+        assert_eq!(histogram.bins, [2, 1, 0, 0, 1]);
+        assert_eq!((histogram.underflow, histogram.overflow), (1, 1));
+        assert_eq!(histogram.entries(), 6);
+        assert_eq!(histogram.max_count(), 2);
+    }
+
+    #[test]
+    fn frames_covered_spans_gaps_and_wraps() {
+        let frames = |counters: &[u32]| {
+            let chunks: Vec<Chunk> = counters
+                .iter()
+                .map(|&fc| Chunk::new(Vec::new(), fc))
+                .collect();
+            frames_covered(&chunks)
+        };
+        assert_eq!(frames(&[]), 0);
+        assert_eq!(frames(&[7]), 1);
+        assert_eq!(frames(&[10, 11, 20]), 11);
+        assert_eq!(frames(&[(1 << 28) - 2, 1]), 4);
+        assert_eq!(frames(&[10, 5, 6]), 2);
+    }
+
+    #[test]
+    fn dead_pixels_count_every_arbiter_bit() {
+        // One word with all five pixels of the first arbiter flagged
+        let mut dw = DataWord::for_pixel(0, 0, 0, 0);
+        dw.address_arbiter = 0b11111;
+        let dead = dead_pixels(&[Chunk::new(vec![dw], 0)]);
+        assert_eq!(dead.len(), MAPPED_COLUMNS as usize * CHIP_ROWS as usize - 5);
+        for y in [0, 9, 18, 27, 36] {
+            assert!(!dead.contains(&(0, y)), "(0, {})", y);
+        }
+        assert!(dead.contains(&(0, 1)));
+    }
+
+    #[test]
+    fn noisy_pixels_are_above_the_mean() {
+        let mut words: Vec<DataWord> = mapped_pixels()
+            .map(|(x, y)| DataWord::for_pixel(x, y, 0, 0))
+            .collect();
+        words.extend(std::iter::repeat_n(DataWord::for_pixel(2, 3, 0, 0), 20));
+        let counts = HitCounts::from_chunks(&[Chunk::new(words, 0)]);
+        assert_eq!(noisy_pixels(&counts, 5.0), [((2, 3), 21)]);
+    }
+
+    #[test]
+    fn rate_over_frames() {
+        let frames_per_second = 1e12 / FRAME_PERIOD_PS as f64;
+        assert!((rate_hz(10, frames_per_second as u64) - 10.0).abs() < 1e-3);
+        assert_eq!(rate_hz(10, 0), 0.0);
+    }
+}
@@ -1,20 +1,106 @@
+use std::io::Write;
+use std::path::Path;
+
+// 9 hit arbiters in each column, each arbiter serving 5 pixels spaced 9 apart
+pub const HA_PR_COL: u8 = 9;
+pub const PIXELS_PR_ARBITER: u8 = 5;
+pub const QCHIP_COLUMNS: u8 = 10;
+// Only the first quarterchip is mapped so far (see README)
+pub const MAPPED_COLUMNS: u8 = QCHIP_COLUMNS;
+
+// Time units of the TDC, all times are handled in ps
+pub const COARSE_TIME_PS: u64 = 3_125;
+pub const FINE_TIME_PS: u64 = 98;
+// A frame spans 2048 coarse clock cycles = 6.4 us
+pub const FRAME_PERIOD_PS: u64 = 2048 * COARSE_TIME_PS;
+// Longest times the leading (12 bit) and trailing (6 bit) coarse fields can hold
+pub const MAX_START_TIME_PS: u64 = 4096 * COARSE_TIME_PS;
+pub const MAX_DURATION_PS: u64 = 64 * COARSE_TIME_PS;
+
+/// A named range of bits `high..=low` in a 48 bit word.
+#[derive(Clone, Copy, Debug)]
+pub struct BitField {
+    pub name: &'static str,
+    pub high: u8,
+    pub low: u8,
+}
+
+impl BitField {
+    const fn new(name: &'static str, high: u8, low: u8) -> Self {
+        BitField { name, high, low }
+    }
+
+    pub fn width(&self) -> u8 {
+        self.high - self.low + 1
+    }
+
+    pub fn value(&self, raw: u64) -> u64 {
+        (raw >> self.low) & ((1 << self.width()) - 1)
+    }
+}
+
+/// Layout of a frame word, most significant field first.
+pub const FRAME_WORD_FIELDS: [BitField; 5] = [
+    BitField::new("frame_marker", 47, 45),
+    BitField::new("unused", 44, 43),
+    BitField::new("qchip_collision_count", 42, 37),
+    BitField::new("hit_counter", 36, 28),
+    BitField::new("frame_counter", 27, 0),
+];
+
+/// Layout of a data word, most significant field first.
+pub const DATA_WORD_FIELDS: [BitField; 10] = [
+    BitField::new("data_selector", 47, 47),
+    BitField::new("address", 46, 40),
+    BitField::new("address_arbiter", 39, 35),
+    BitField::new("address_pileup", 34, 30),
+    BitField::new("leading_coarse_time_selector", 29, 29),
+    BitField::new("leading_coarse_time", 28, 17),
+    BitField::new("leading_fine_time", 16, 12),
+    BitField::new("trailing_coarse_time_selector", 11, 11),
+    BitField::new("trailing_coarse_time", 10, 5),
+    BitField::new("trailing_fine_time", 4, 0),
+];
+
+// 47..45: always "111"
 // 42..37: qchip_collision_count
 // 36..28: hit_counter
 // 27..0 : frame_counter
 #[derive(Clone, Copy, Debug)]
 pub struct FrameWord {
-    raw: u64,
-    qchip_collision_count: u8,
-    hit_counter: u16,
-    frame_counter: u32,
+    pub raw: u64,
+    pub qchip_collision_count: u8,
+    pub hit_counter: u16,
+    pub frame_counter: u32,
+}
+
+impl FrameWord {
+    pub fn new(qchip_collision_count: u8, hit_counter: u16, frame_counter: u32) -> Self {
+        let raw = 0b111 << 45
+            | ((qchip_collision_count as u64) & 0x3F) << 37
+            | ((hit_counter as u64) & 0x1FF) << 28
+            | ((frame_counter as u64) & 0xFFFFFFF);
+        FrameWord::from(raw)
+    }
 }
 
-impl From<&str> for FrameWord {
-    fn from(value: &str) -> Self {
-        let raw = u64::from_str_radix(value, 16).unwrap();
+/// Signed number of frames from `reference` to `frame_counter`, taking the
+/// 28 bit wraparound of the frame counter into account.
+pub fn frame_offset(frame_counter: u32, reference: u32) -> i64 {
+    let diff = frame_counter.wrapping_sub(reference) << 4;
+    (diff as i32 >> 4) as i64
+}
+
+/// Frame words are told apart from data words by bits 47..45 being set.
+pub fn is_frame_word(raw: u64) -> bool {
+    raw >> 45 & 0b111 == 0b111
+}
+
+impl From<u64> for FrameWord {
+    fn from(raw: u64) -> Self {
         let qchip_collision_count = ((raw >> 37) & 0x3F) as u8;
-        let hit_counter = ((raw >> 28) & 0xFF) as u16;
-        let frame_counter = (raw & 0x7FFFFFF) as u32;
+        let hit_counter = ((raw >> 28) & 0x1FF) as u16;
+        let frame_counter = (raw & 0xFFFFFFF) as u32;
         FrameWord {
             raw,
             qchip_collision_count,
@@ -24,6 +110,14 @@ impl From<&str> for FrameWord {
     }
 }
 
+/// Parses one hex word of the text format.
+pub fn parse_hex_word(word: &str) -> Result<u64, String> {
+    match u64::from_str_radix(word, 16) {
+        Ok(raw) if raw >> 48 == 0 => Ok(raw),
+        _ => Err(format!("'{}' is not a 48 bit hex word", word)),
+    }
+}
+
 // 47    : data selector
 // 46..40: address
 // 39..35: address_arbiter
@@ -87,11 +181,109 @@ impl DataWord {
         leading_coarse_time + leading_fine_time
         // This returns the time in ps
     }
+
+    /// Builds a word for a hit on pixel (x, y) of the first quarterchip,
+    /// quantizing the leading time and duration (both in ps) to TDC units.
+    pub fn for_pixel(x: u8, y: u8, start_time: u64, duration: u64) -> Self {
+        let (address, address_arbiter) = pixel_to_address(x, y);
+        let (leading_coarse_time, leading_fine_time) = quantize_time(start_time);
+        let (mut trailing_coarse_time, mut trailing_fine_time) = quantize_time(duration);
+        // The duration saturates at the width of the trailing fields
+        if trailing_coarse_time > 0x3F {
+            trailing_coarse_time = 0x3F;
+            trailing_fine_time = 0x1F;
+        }
+        let mut dw = DataWord {
+            raw: 0,
+            data_selector: 1,
+            address,
+            address_arbiter,
+            address_pileup: 0,
+            leading_coarse_time_selector: 0,
+            leading_coarse_time: leading_coarse_time.min(0xFFF) as u16,
+            leading_fine_time,
+            trailing_coarse_time_selector: 0,
+            trailing_coarse_time: trailing_coarse_time as u8,
+            trailing_fine_time,
+        };
+        dw.raw = dw.encode();
+        dw
+    }
+
+    /// Packs the fields back into a 48 bit word.
+    pub fn encode(&self) -> u64 {
+        ((self.data_selector as u64) & 0x1) << 47
+            | ((self.address as u64) & 0x7F) << 40
+            | ((self.address_arbiter as u64) & 0x1F) << 35
+            | ((self.address_pileup as u64) & 0x1F) << 30
+            | ((self.leading_coarse_time_selector as u64) & 0x1) << 29
+            | ((self.leading_coarse_time as u64) & 0xFFF) << 17
+            | ((self.leading_fine_time as u64) & 0x1F) << 12
+            | ((self.trailing_coarse_time_selector as u64) & 0x1) << 11
+            | ((self.trailing_coarse_time as u64) & 0x3F) << 5
+            | ((self.trailing_fine_time as u64) & 0x1F)
+    }
+
+    /// Pixel coordinate of the hit.
+    pub fn pixel(&self) -> (u8, u8) {
+        let group_nr = self.address;
+        // 5 groups in each column
+        // Arbiter shows which of the 5 pixels in group where hit
+        // "00001" means the first pixel was triggered
+        // "10000" means the last pixel was triggered
+        let arbiter_val = if self.address_arbiter == 0 {
+            0
+        } else {
+            self.address_arbiter.trailing_zeros() as u8
+        };
+
+        let x = group_nr / HA_PR_COL; // Integer division
+
+        // Each pixel in a group are spaced 9 pixels apart (vertically)
+        let arbiter_nr = group_nr % HA_PR_COL; // Which arbiter is hit
+        let y = arbiter_nr + arbiter_val * HA_PR_COL; // Which pixel in the arbiter is hit
+        (x, y)
+    }
+
+    /// All pixels flagged in the arbiter pattern of this word.
+    pub fn hit_pixels(&self) -> Vec<(u8, u8)> {
+        let x = self.address / HA_PR_COL;
+        let arbiter_nr = self.address % HA_PR_COL;
+        (0..PIXELS_PR_ARBITER)
+            .filter(|p| self.address_arbiter & (1 << p) != 0)
+            .map(|p| (x, arbiter_nr + p * HA_PR_COL))
+            .collect()
+    }
+
+    /// Pixels served by the arbiter of this word if it flagged pileup.
+    pub fn pileup_pixels(&self) -> Vec<(u8, u8)> {
+        if self.address_pileup == 0 {
+            return Vec::new();
+        }
+        let x = self.address / HA_PR_COL;
+        let arbiter_nr = self.address % HA_PR_COL;
+        (0..PIXELS_PR_ARBITER)
+            .map(|p| (x, arbiter_nr + p * HA_PR_COL))
+            .collect()
+    }
+}
+
+/// Address and arbiter pattern of pixel (x, y), inverse of `DataWord::pixel`.
+pub fn pixel_to_address(x: u8, y: u8) -> (u8, u8) {
+    let address = x * HA_PR_COL + y % HA_PR_COL;
+    let address_arbiter = 1 << (y / HA_PR_COL);
+    (address, address_arbiter)
+}
+
+/// Splits a time in ps into (coarse, fine) TDC codes.
+pub fn quantize_time(time: u64) -> (u64, u8) {
+    let coarse = time / COARSE_TIME_PS;
+    let fine = ((time % COARSE_TIME_PS) / FINE_TIME_PS).min(0x1F) as u8;
+    (coarse, fine)
 }
 
-impl From<&str> for DataWord {
-    fn from(value: &str) -> Self {
-        let raw = u64::from_str_radix(value, 16).unwrap();
+impl From<u64> for DataWord {
+    fn from(raw: u64) -> Self {
         let data_selector = ((raw >> 47) & 0x1) as u8;
         let address = ((raw >> 40) & 0x7F) as u8;
         let address_arbiter = ((raw >> 35) & 0x1F) as u8;
@@ -129,20 +321,70 @@ pub struct Chunk {
     pub frame_word: FrameWord,
 }
 
-pub fn parse_tdcpix_txt(file: &str, chunks: &mut Vec<Chunk>) {
-    for line in std::fs::read_to_string(file).unwrap().lines() {
-        let mut words: Vec<&str> = line.split_whitespace().collect();
-
-        let frame_word = FrameWord::from(words.pop().unwrap());
+impl Chunk {
+    /// Wraps `data_words` with a frame word whose hit and collision counts match them.
+    pub fn new(data_words: Vec<DataWord>, frame_counter: u32) -> Self {
+        let collisions = data_words
+            .iter()
+            .filter(|dw| dw.address_pileup != 0)
+            .count();
+        let frame_word = FrameWord::new(
+            collisions.min(0x3F) as u8,
+            data_words.len().min(0x1FF) as u16,
+            frame_counter,
+        );
+        Chunk {
+            data_words,
+            frame_word,
+        }
+    }
 
-        let mut data_words: Vec<DataWord> = Vec::new();
-        for word in words {
-            data_words.push(DataWord::from(word));
+    /// One line of the text format: data words followed by the frame word.
+    pub fn to_line(&self) -> String {
+        let mut line = String::new();
+        for dw in &self.data_words {
+            line.push_str(&format!("{:012x} ", dw.raw));
         }
+        line.push_str(&format!("{:012x}", self.frame_word.raw));
+        line
+    }
+}
 
-        chunks.push(Chunk {
-            data_words,
-            frame_word,
-        });
+/// Parses one line of the text format, empty lines give `None`.
+pub fn parse_tdcpix_line(line: &str) -> Result<Option<Chunk>, String> {
+    let mut words: Vec<&str> = line.split_whitespace().collect();
+
+    let Some(frame_word) = words.pop() else {
+        return Ok(None);
+    };
+    let frame_word = FrameWord::from(parse_hex_word(frame_word)?);
+
+    let mut data_words: Vec<DataWord> = Vec::new();
+    for word in words {
+        data_words.push(DataWord::from(parse_hex_word(word)?));
+    }
+
+    Ok(Some(Chunk {
+        data_words,
+        frame_word,
+    }))
+}
+
+pub fn parse_tdcpix_txt(file: &Path, chunks: &mut Vec<Chunk>) -> Result<(), String> {
+    let text = std::fs::read_to_string(file)
+        .map_err(|e| format!("Could not read {}: {}", file.display(), e))?;
+    for (i, line) in text.lines().enumerate() {
+        let chunk =
+            parse_tdcpix_line(line).map_err(|e| format!("{}:{}: {}", file.display(), i + 1, e))?;
+        chunks.extend(chunk);
+    }
+    Ok(())
+}
+
+pub fn write_tdcpix_txt(file: &Path, chunks: &[Chunk]) -> std::io::Result<()> {
+    let mut writer = std::io::BufWriter::new(std::fs::File::create(file)?);
+    for chunk in chunks {
+        writeln!(writer, "{}", chunk.to_line())?;
     }
+    writer.flush()
 }
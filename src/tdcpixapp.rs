@@ -1,62 +1,544 @@
 use egui_file::FileDialog;
 use std::path::PathBuf;
+use std::time::{Duration, Instant};
 
-use crate::tdcpix::Chunk;
-use crate::widgets::{pixelgrid::PixelGrid, timeline::Timeline, utility_bar::UtilityBar};
+use crate::calibration::Calibration;
+use crate::colors::ColorMode;
+use crate::export::{AnimationConfig, AnimationExport, AnimationFormat, HitsFormat, ViewFormat};
+use crate::follow::{FileFollower, FollowUpdate};
+use crate::generator::GeneratorConfig;
+use crate::hitmap::{Afterglow, HitMap, CHIP_COLUMNS, CHIP_ROWS};
+use crate::net::{NetConfig, NetSource, WireFormat};
+use crate::render::RenderSettings;
+use crate::sliding::SlidingVec;
+use crate::tdcpix::{Chunk, DataWord};
+use crate::validation::{summarize_chunks, ChunkSummary};
+use crate::widgets::{
+    builder_window::BuilderWindow,
+    chunk_browser::{ChunkBrowser, ChunkColumn, ChunkFilter, ChunkOrder},
+    export_window::{ExportWindow, HitExportOptions},
+    generator_window::GeneratorWindow,
+    inspector_window::InspectorWindow,
+    net_window::NetWindow,
+    pixelgrid::PixelGrid,
+    timeline::{LaneGrouping, TimeView, Timeline, TimelineLayout},
+    utility_bar::UtilityBar,
+    word_table::{WordColumn, WordTableWindow},
+};
 
 use eframe::egui;
 
+// How often a followed file is checked for new chunks
+const FOLLOW_POLL_INTERVAL: Duration = Duration::from_millis(200);
+
+/// A selected pixel and the data word it fired in.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Selection {
+    pub pixel: (u8, u8),
+    // (chunk idx, word idx), None for a pixel without a hit
+    pub word: Option<(usize, usize)>,
+}
+
+/// What `TDCpixApp::jump_to_next` looks for in a chunk.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum JumpTarget {
+    // A word with pixel (x, y) in its arbiter pattern
+    Pixel(u8, u8),
+    Pileup,
+    // A chunk that failed validation
+    Issue,
+}
+
 pub struct TDCpixApp {
     pub file_path: Option<PathBuf>,
     pub open_file_dialog: Option<FileDialog>,
-    pub chunks: Vec<Chunk>,
-    pub w_dim: egui::Vec2,
+    // Why the last file could not be opened
+    pub file_error: Option<String>,
+    // Live sources drop the oldest chunks beyond their capacity
+    pub chunks: SlidingVec<Chunk>,
+    // Bumped whenever `chunks` are replaced or their indices move, so cached
+    // views know to update. Appending chunks keeps it
+    pub chunks_generation: u64,
     pub analysis_chunk_idx: usize,
-    pub hit_idxes: Vec<(u8, u8)>,
     // pub arbiter_idxes: Vec<(u8, u8)>,
-    pub pileup_idxes: Vec<(u8, u8)>,
+    pub hit_map: HitMap,
     pub idx_field_value: String,
-    pub highlight_idx: (u8, u8),
-    pub has_selected_hit: bool,
+    // The last jump to the next matching chunk found nothing
+    pub jump_failed: bool,
+    pub playing: bool,
+    pub playback_fps: f32,
+    last_playback_step: Instant,
+    // Fading trace of the chunks stepped through during playback
+    pub show_afterglow: bool,
+    // Number of chunks after which the trace has faded to half
+    pub afterglow_half_life: f32,
+    pub afterglow: Afterglow,
+    // Shared by the pixel grid, timeline, word table and inspector
+    pub selection: Option<Selection>,
+    pub generator_config: GeneratorConfig,
+    pub show_generator: bool,
+    pub generator_dead_pixels: String,
+    pub generator_format: WireFormat,
+    // Why the generated chunks could not be saved
+    pub generator_error: Option<String>,
+    pub save_file_dialog: Option<FileDialog>,
+    pub follower: Option<FileFollower>,
+    last_follow_poll: Instant,
+    pub auto_advance: bool,
+    pub net_config: NetConfig,
+    pub net_source: Option<NetSource>,
+    pub show_network: bool,
+    pub show_inspector: bool,
+    pub word_table_sort: (WordColumn, bool),
+    // Selection the word table last scrolled to
+    pub word_table_scrolled: Option<Selection>,
+    pub show_word_table: bool,
+    // Cached per chunk numbers, valid for `chunk_summaries_generation`
+    pub chunk_summaries: SlidingVec<ChunkSummary>,
+    pub chunk_summaries_generation: Option<u64>,
+    pub chunk_browser_sort: (ChunkColumn, bool),
+    pub chunk_filter: ChunkFilter,
+    pub chunk_order: ChunkOrder,
+    pub show_chunk_browser: bool,
+    // Word being edited in the builder and the words collected into its chunk
+    pub builder_word: DataWord,
+    pub builder_words: Vec<DataWord>,
+    pub builder_frame_counter: u32,
+    pub builder_save_dialog: Option<FileDialog>,
+    pub show_builder: bool,
+    // Size and content of exported images, the timeline settings are taken
+    // from the window when exporting
+    pub export_settings: RenderSettings,
+    pub animation_config: AnimationConfig,
+    pub view_save_dialog: Option<(FileDialog, ViewFormat)>,
+    pub animation_save_dialog: Option<FileDialog>,
+    pub animation_export: Option<AnimationExport>,
+    pub hit_export_options: HitExportOptions,
+    pub hits_save_dialog: Option<(FileDialog, HitsFormat)>,
+    // Outcome of the last export
+    pub export_status: Option<String>,
+    pub show_export: bool,
+    pub timeline_layout: Option<TimelineLayout>,
+    // None shows the whole chunk
+    pub timeline_view: Option<TimeView>,
+    // Number of consecutive chunks shown on the timeline
+    pub timeline_chunks: usize,
+    pub timeline_grouping: LaneGrouping,
+    // Measurement cursor times in ps relative to the current frame, None when hidden
+    pub timeline_cursors: Option<[f64; 2]>,
+    // Leading time window in ps relative to the current frame that the pixel
+    // grid is restricted to, brushed on the timeline
+    pub time_filter: Option<(f64, f64)>,
+    // Shared by the timeline and pixel grid
+    pub color_mode: ColorMode,
+    pub calibration: Calibration,
+    pub calibration_dialog: Option<FileDialog>,
+    // Why the last calibration file could not be loaded
+    pub calibration_error: Option<String>,
 }
 
-impl TDCpixApp {
-    pub fn new(_cc: &eframe::CreationContext<'_>, w_dim: egui::Vec2) -> Self {
-        let default_idx = 951002;
-
+impl Default for TDCpixApp {
+    fn default() -> Self {
         TDCpixApp {
             file_path: Default::default(),
             open_file_dialog: Default::default(),
-            chunks: Vec::new(),
-            w_dim,
+            file_error: None,
+            chunks: SlidingVec::new(),
+            chunks_generation: 0,
             analysis_chunk_idx: 0,
-            hit_idxes: Vec::new(),
             // arbiter_idxes: Vec::new(),
-            pileup_idxes: Vec::new(),
-            idx_field_value: default_idx.to_string(),
-            highlight_idx: (0, 0),
-            has_selected_hit: false,
+            hit_map: HitMap::new(CHIP_COLUMNS, CHIP_ROWS),
+            idx_field_value: "0".to_string(),
+            jump_failed: false,
+            playing: false,
+            playback_fps: 10.0,
+            last_playback_step: Instant::now(),
+            show_afterglow: true,
+            afterglow_half_life: 3.0,
+            afterglow: Afterglow::new(CHIP_COLUMNS, CHIP_ROWS),
+            selection: None,
+            generator_config: GeneratorConfig::default(),
+            show_generator: false,
+            generator_dead_pixels: String::new(),
+            generator_format: WireFormat::Hex,
+            generator_error: None,
+            save_file_dialog: None,
+            follower: None,
+            last_follow_poll: Instant::now(),
+            auto_advance: true,
+            net_config: NetConfig::default(),
+            net_source: None,
+            show_network: false,
+            show_inspector: false,
+            word_table_sort: (WordColumn::Index, true),
+            word_table_scrolled: None,
+            show_word_table: false,
+            chunk_summaries: SlidingVec::new(),
+            chunk_summaries_generation: None,
+            chunk_browser_sort: (ChunkColumn::Index, true),
+            chunk_filter: ChunkFilter::default(),
+            chunk_order: ChunkOrder::default(),
+            show_chunk_browser: false,
+            builder_word: DataWord::for_pixel(0, 0, 0, 25_000),
+            builder_words: Vec::new(),
+            builder_frame_counter: 0,
+            builder_save_dialog: None,
+            show_builder: false,
+            export_settings: RenderSettings {
+                width: 576,
+                include_timeline: true,
+                color_mode: ColorMode::Pileup,
+                grouping: LaneGrouping::Address,
+                timeline_chunks: 1,
+                timeline_view: None,
+                file_name: None,
+                calibration: Calibration::default(),
+                first_chunk_idx: 0,
+            },
+            animation_config: AnimationConfig {
+                first: 0,
+                last: 0,
+                fps: 10.0,
+                format: AnimationFormat::Gif,
+                afterglow_half_life: Some(3.0),
+            },
+            view_save_dialog: None,
+            animation_save_dialog: None,
+            animation_export: None,
+            hit_export_options: HitExportOptions::default(),
+            hits_save_dialog: None,
+            export_status: None,
+            show_export: false,
+            timeline_layout: None,
+            timeline_view: None,
+            timeline_chunks: 1,
+            timeline_grouping: LaneGrouping::Address,
+            timeline_cursors: None,
+            time_filter: None,
+            color_mode: ColorMode::Pileup,
+            calibration: Calibration::default(),
+            calibration_dialog: None,
+            calibration_error: None,
+        }
+    }
+}
+
+impl TDCpixApp {
+    pub fn new(_cc: &eframe::CreationContext<'_>) -> Self {
+        Self::default()
+    }
+
+    /// Replaces the loaded chunks and jumps to the first one.
+    pub fn set_chunks(&mut self, chunks: Vec<Chunk>, file_path: Option<PathBuf>) {
+        self.file_path = file_path;
+        self.follower = None;
+        self.net_source = None;
+        self.clear_chunks();
+        self.chunks = chunks.into();
+        self.update_analysis_chunk_idx(0);
+    }
+
+    /// Drops the chunks and everything shown of them, also when live sources
+    /// start over.
+    fn clear_chunks(&mut self) {
+        self.chunks.clear();
+        self.chunks_generation += 1;
+        self.analysis_chunk_idx = 0;
+        self.idx_field_value = "0".to_string();
+        self.clear_selection();
+        self.hit_map.clear();
+        self.afterglow.clear();
+    }
+
+    /// Selects `pixel` and the word it fired in, selecting the same again
+    /// clears the selection.
+    pub fn toggle_selection(&mut self, pixel: (u8, u8), word: Option<(usize, usize)>) {
+        let selection = Selection { pixel, word };
+        if self.selection == Some(selection) {
+            self.selection = None;
+        } else {
+            self.selection = Some(selection);
+        }
+    }
+
+    /// Steps to the next chunk, keeping the hits of the current one in the
+    /// afterglow. Playback stops at the last chunk.
+    pub fn advance_playback(&mut self) {
+        if self.analysis_chunk_idx + 1 >= self.chunks.len() {
+            self.playing = false;
+            return;
+        }
+        let mut afterglow = std::mem::replace(&mut self.afterglow, Afterglow::new(0, 0));
+        afterglow.add(&self.hit_map, 0.5f32.powf(1.0 / self.afterglow_half_life));
+        self.step_analysis_chunk_idx(1);
+        self.afterglow = afterglow;
+        self.last_playback_step = Instant::now();
+    }
+
+    /// Moves `step` chunks forward or back, stopping at the first and last chunk.
+    pub fn step_analysis_chunk_idx(&mut self, step: isize) {
+        if self.chunks.is_empty() {
+            return;
+        }
+        let idx = self
+            .analysis_chunk_idx
+            .saturating_add_signed(step)
+            .min(self.chunks.len() - 1);
+        self.update_analysis_chunk_idx(idx);
+    }
+
+    /// Moves to the next chunk after the current one matching `target`,
+    /// continuing from the start when the end is reached. Returns false if
+    /// no chunk matches.
+    pub fn jump_to_next(&mut self, target: JumpTarget) -> bool {
+        if target == JumpTarget::Issue {
+            self.update_chunk_summaries();
+        }
+        let n = self.chunks.len();
+        let found = (1..=n)
+            .map(|offset| (self.analysis_chunk_idx + offset) % n)
+            .find(|&idx| {
+                let words = &self.chunks[idx].data_words;
+                match target {
+                    JumpTarget::Pixel(x, y) => words.iter().any(|dw| dw.pixel() == (x, y)),
+                    JumpTarget::Pileup => words.iter().any(|dw| dw.address_pileup != 0),
+                    JumpTarget::Issue => !self.chunk_summaries[idx].issues.is_empty(),
+                }
+            });
+        match found {
+            Some(idx) => {
+                self.update_analysis_chunk_idx(idx);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Recomputes the chunk summaries if the chunks changed since last time.
+    pub fn update_chunk_summaries(&mut self) {
+        if self.chunk_summaries_generation != Some(self.chunks_generation) {
+            self.chunk_summaries = summarize_chunks(&self.chunks).into();
+            self.chunk_summaries_generation = Some(self.chunks_generation);
+        }
+    }
+
+    /// Starts tailing the loaded file, the file is read again from the start.
+    pub fn start_following(&mut self) {
+        if let Some(path) = self.file_path.clone() {
+            self.follower = Some(FileFollower::new(path));
+            self.clear_chunks();
+            self.poll_follower();
+            self.last_follow_poll = Instant::now();
+        }
+    }
+
+    pub fn stop_following(&mut self) {
+        self.follower = None;
+    }
+
+    /// Appends the chunks written to the followed file since the last poll.
+    pub fn poll_follower(&mut self) {
+        let Some(follower) = &mut self.follower else {
+            return;
+        };
+        let update = follower.poll();
+        follower.error = update
+            .as_ref()
+            .err()
+            .map(|e| format!("Could not follow {}: {}", follower.path.display(), e));
+        let new_chunks = match update {
+            Ok(FollowUpdate::Appended(new_chunks)) => new_chunks,
+            Ok(FollowUpdate::Truncated) => {
+                self.clear_chunks();
+                return;
+            }
+            Err(_) => return,
+        };
+        self.append_live_chunks(new_chunks, None);
+    }
+
+    /// Starts listening for words on the network, the loaded chunks are dropped.
+    pub fn start_network(&mut self) {
+        self.set_chunks(Vec::new(), None);
+        self.net_source = Some(NetSource::start(&self.net_config));
+    }
+
+    pub fn stop_network(&mut self) {
+        self.net_source = None;
+    }
+
+    /// Appends the chunks received on the network since the last poll.
+    pub fn poll_network(&mut self) {
+        let Some(net_source) = &mut self.net_source else {
+            return;
+        };
+        let new_chunks = net_source.take_chunks();
+        self.append_live_chunks(new_chunks, Some(self.net_config.capacity));
+    }
+
+    /// Appends chunks from a live source, dropping the oldest ones beyond `capacity`.
+    fn append_live_chunks(&mut self, new_chunks: Vec<Chunk>, capacity: Option<usize>) {
+        if new_chunks.is_empty() {
+            return;
+        }
+
+        let was_empty = self.chunks.is_empty();
+        let first_new = self.chunks.len();
+        self.chunks.extend(new_chunks);
+        // Summaries that are up to date only need the new chunks added
+        let summaries_valid = self.chunk_summaries_generation == Some(self.chunks_generation);
+        if summaries_valid {
+            for i in first_new..self.chunks.len() {
+                let previous = i.checked_sub(1).map(|p| &self.chunks[p]);
+                self.chunk_summaries
+                    .push(ChunkSummary::new(&self.chunks[i], previous));
+            }
+        }
+
+        let mut idx = self.analysis_chunk_idx;
+        if let Some(capacity) = capacity {
+            let excess = self.chunks.len().saturating_sub(capacity.max(1));
+            if excess > 0 {
+                self.chunks.drop_front(excess);
+                self.chunk_summaries.drop_front(excess);
+                // The kept chunks moved to lower indices
+                self.chunks_generation += 1;
+                if summaries_valid {
+                    self.chunk_summaries_generation = Some(self.chunks_generation);
+                }
+                idx = idx.saturating_sub(excess);
+                self.shift_selection(excess);
+            }
+        }
+
+        if self.auto_advance {
+            idx = self.chunks.len() - 1;
+        } else if was_empty {
+            idx = 0;
+        }
+        self.update_analysis_chunk_idx(idx);
+    }
+
+    fn clear_selection(&mut self) {
+        self.selection = None;
+        self.word_table_scrolled = None;
+    }
+
+    /// Keeps the selected word pointing at the same word after the first
+    /// `removed` chunks were dropped. A word in a dropped chunk is
+    /// deselected, its pixel stays selected.
+    fn shift_selection(&mut self, removed: usize) {
+        let shift = |selection: Option<Selection>| {
+            selection.map(|s| Selection {
+                pixel: s.pixel,
+                word: s.word.and_then(|(chunk_idx, word_idx)| {
+                    Some((chunk_idx.checked_sub(removed)?, word_idx))
+                }),
+            })
+        };
+        self.selection = shift(self.selection);
+        self.word_table_scrolled = shift(self.word_table_scrolled);
+    }
+
+    pub fn update_analysis_chunk_idx(&mut self, idx: usize) {
+        // Check if index is in bounds
+        if idx >= self.chunks.len() {
+            return;
         }
+        self.analysis_chunk_idx = idx;
+        self.idx_field_value = idx.to_string();
+        // The trace only makes sense for consecutive chunks, see `advance_playback`
+        self.afterglow.clear();
+        // self.arbiter_idxes.clear();
+
+        let chunk = &self.chunks[self.analysis_chunk_idx];
+        self.hit_map = HitMap::from_chunk(chunk);
     }
 }
 
 impl eframe::App for TDCpixApp {
     fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
-        egui::CentralPanel::default().show(ctx, |ui| {
-            // Pixel grid
-            ui.horizontal(|ui| {
-                ui.add(PixelGrid::new(40, 45, self));
-            });
+        if self.follower.is_some() {
+            let elapsed = self.last_follow_poll.elapsed();
+            if elapsed >= FOLLOW_POLL_INTERVAL {
+                self.poll_follower();
+                self.last_follow_poll = Instant::now();
+                ctx.request_repaint_after(FOLLOW_POLL_INTERVAL);
+            } else {
+                ctx.request_repaint_after(FOLLOW_POLL_INTERVAL - elapsed);
+            }
+        }
+        if self.net_source.is_some() {
+            self.poll_network();
+            ctx.request_repaint_after(Duration::from_millis(100));
+        }
 
-            // Utility bar
-            ui.horizontal(|ui| {
-                ui.add(UtilityBar::new(self));
+        if let Some(export) = &mut self.animation_export {
+            match export.finished() {
+                Some(result) => {
+                    self.export_status = Some(match result {
+                        Ok(frames) => format!("Wrote {} frames", frames),
+                        Err(e) => e,
+                    });
+                    self.animation_export = None;
+                }
+                None => ctx.request_repaint_after(Duration::from_millis(100)),
+            }
+        }
+
+        if self.playing {
+            let period = Duration::from_secs_f32(1.0 / self.playback_fps.max(0.1));
+            let elapsed = self.last_playback_step.elapsed();
+            if elapsed >= period {
+                self.advance_playback();
+                ctx.request_repaint_after(period);
+            } else {
+                ctx.request_repaint_after(period - elapsed);
+            }
+        }
+
+        // Arrow keys step through the chunks unless a text field is being edited
+        if ctx.memory(|m| m.focus().is_none()) {
+            let (left, right) = ctx.input(|i| {
+                (
+                    i.key_pressed(egui::Key::ArrowLeft),
+                    i.key_pressed(egui::Key::ArrowRight),
+                )
             });
+            if left {
+                self.step_analysis_chunk_idx(-1);
+            }
+            if right {
+                self.step_analysis_chunk_idx(1);
+            }
+        }
 
-            // Timeline
-            ui.horizontal(|ui| {
+        // Timeline at the bottom, its height can be dragged to share the
+        // space with the pixel grid
+        egui::TopBottomPanel::bottom("timeline_panel")
+            .resizable(true)
+            .default_height(ctx.screen_rect().height() * 0.3)
+            .min_height(50.0)
+            .show(ctx, |ui| {
                 ui.add(Timeline::new(self));
             });
+
+        // Utility bar
+        egui::TopBottomPanel::bottom("utility_bar_panel").show(ctx, |ui| {
+            ui.add(UtilityBar::new(self));
         });
+
+        // Pixel grid
+        egui::CentralPanel::default().show(ctx, |ui| {
+            ui.add(PixelGrid::new(40, 45, self));
+        });
+
+        GeneratorWindow::new(self).show(ctx);
+        NetWindow::new(self).show(ctx);
+        InspectorWindow::new(self).show(ctx);
+        BuilderWindow::new(self).show(ctx);
+        WordTableWindow::new(self).show(ctx);
+        ChunkBrowser::new(self).show(ctx);
+        ExportWindow::new(self).show(ctx);
     }
 }
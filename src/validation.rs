@@ -0,0 +1,246 @@
+use std::fmt;
+
+use crate::tdcpix::{frame_offset, is_frame_word, Chunk, HA_PR_COL, MAPPED_COLUMNS};
+
+/// Something in a chunk that the chip should not have produced.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Issue {
+    // The frame word counts a different number of words than the chunk has
+    HitCounterMismatch { hit_counter: u16, words: usize },
+    // The frame counter does not follow the one of the previous chunk
+    FrameGap { previous: u32, frame_counter: u32 },
+    // A data word with the frame word marker set
+    StrayFrameWord { word_idx: usize },
+    // A data word addressing an arbiter outside the mapped quarterchip
+    UnmappedAddress { word_idx: usize, address: u8 },
+    // A data word without any pixel flagged in its arbiter pattern
+    EmptyArbiter { word_idx: usize },
+    // A data word starting before the word read out ahead of it
+    OutOfOrder { word_idx: usize },
+}
+
+impl Issue {
+    /// Short name of the kind of issue, the same for all issues of a variant.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Issue::HitCounterMismatch { .. } => "hit counter mismatch",
+            Issue::FrameGap { .. } => "frame gap",
+            Issue::StrayFrameWord { .. } => "stray frame word",
+            Issue::UnmappedAddress { .. } => "unmapped address",
+            Issue::EmptyArbiter { .. } => "empty arbiter",
+            Issue::OutOfOrder { .. } => "out of order",
+        }
+    }
+}
+
+impl fmt::Display for Issue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Issue::HitCounterMismatch { hit_counter, words } => write!(
+                f,
+                "hit_counter is {} but the chunk has {} data words",
+                hit_counter, words
+            ),
+            Issue::FrameGap {
+                previous,
+                frame_counter,
+            } => write!(
+                f,
+                "frame counter jumps from {} to {}",
+                previous, frame_counter
+            ),
+            Issue::StrayFrameWord { word_idx } => {
+                write!(f, "word {} has the frame word marker set", word_idx)
+            }
+            Issue::UnmappedAddress { word_idx, address } => {
+                write!(f, "word {} has unmapped address {}", word_idx, address)
+            }
+            Issue::EmptyArbiter { word_idx } => {
+                write!(f, "word {} has no pixel in its arbiter pattern", word_idx)
+            }
+            Issue::OutOfOrder { word_idx } => {
+                write!(f, "word {} starts before the previous word", word_idx)
+            }
+        }
+    }
+}
+
+/// Checks the hit counter of the frame word against the data words of the chunk.
+pub fn check_hit_counter(chunk: &Chunk) -> Option<Issue> {
+    // The hit counter saturates at its 9 bits
+    let words = chunk.data_words.len();
+    let hit_counter = chunk.frame_word.hit_counter;
+    (hit_counter as usize != words.min(0x1FF))
+        .then_some(Issue::HitCounterMismatch { hit_counter, words })
+}
+
+/// Checks a chunk on its own and against the chunk read before it.
+pub fn validate_chunk(chunk: &Chunk, previous: Option<&Chunk>) -> Vec<Issue> {
+    let mut issues = Vec::new();
+    let fw = &chunk.frame_word;
+
+    issues.extend(check_hit_counter(chunk));
+
+    if let Some(previous) = previous {
+        let previous = previous.frame_word.frame_counter;
+        if frame_offset(fw.frame_counter, previous) != 1 {
+            issues.push(Issue::FrameGap {
+                previous,
+                frame_counter: fw.frame_counter,
+            });
+        }
+    }
+
+    let mut last_start = 0;
+    for (word_idx, dw) in chunk.data_words.iter().enumerate() {
+        // The fields of a frame word mean nothing as a data word, so it is
+        // not checked any further
+        if is_frame_word(dw.raw) {
+            issues.push(Issue::StrayFrameWord { word_idx });
+            continue;
+        }
+        if dw.address >= MAPPED_COLUMNS * HA_PR_COL {
+            issues.push(Issue::UnmappedAddress {
+                word_idx,
+                address: dw.address,
+            });
+        }
+        if dw.address_arbiter == 0 {
+            issues.push(Issue::EmptyArbiter { word_idx });
+        }
+        let start = dw.get_start_time();
+        if start < last_start {
+            issues.push(Issue::OutOfOrder { word_idx });
+        }
+        last_start = start;
+    }
+
+    issues
+}
+
+/// Per chunk numbers shown in the chunk browser.
+#[derive(Clone, Debug)]
+pub struct ChunkSummary {
+    pub frame_counter: u32,
+    pub words: usize,
+    pub hit_counter: u16,
+    pub collisions: u8,
+    // Data words that flagged pileup
+    pub pileup_words: usize,
+    pub issues: Vec<Issue>,
+}
+
+impl ChunkSummary {
+    pub fn new(chunk: &Chunk, previous: Option<&Chunk>) -> Self {
+        ChunkSummary {
+            frame_counter: chunk.frame_word.frame_counter,
+            words: chunk.data_words.len(),
+            hit_counter: chunk.frame_word.hit_counter,
+            collisions: chunk.frame_word.qchip_collision_count,
+            pileup_words: chunk
+                .data_words
+                .iter()
+                .filter(|dw| dw.address_pileup != 0)
+                .count(),
+            issues: validate_chunk(chunk, previous),
+        }
+    }
+}
+
+/// Summaries of all chunks, in order.
+pub fn summarize_chunks(chunks: &[Chunk]) -> Vec<ChunkSummary> {
+    chunks
+        .iter()
+        .enumerate()
+        .map(|(i, chunk)| ChunkSummary::new(chunk, i.checked_sub(1).map(|p| &chunks[p])))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tdcpix::{DataWord, FrameWord, COARSE_TIME_PS};
+
+    fn word(x: u8, y: u8, start_coarse: u64) -> DataWord {
+        DataWord::for_pixel(x, y, start_coarse * COARSE_TIME_PS, COARSE_TIME_PS)
+    }
+
+    #[test]
+    fn consistent_chunks_have_no_issues() {
+        let first = Chunk::new(vec![word(0, 0, 1), word(1, 2, 5)], 10);
+        let second = Chunk::new(vec![], 11);
+        assert_eq!(validate_chunk(&first, None), vec![]);
+        assert_eq!(validate_chunk(&second, Some(&first)), vec![]);
+    }
+
+    #[test]
+    fn hit_counter_must_match_the_words() {
+        let mut chunk = Chunk::new(vec![word(0, 0, 1)], 0);
+        chunk.frame_word = FrameWord::new(0, 2, 0);
+        assert_eq!(
+            check_hit_counter(&chunk),
+            Some(Issue::HitCounterMismatch {
+                hit_counter: 2,
+                words: 1
+            })
+        );
+    }
+
+    #[test]
+    fn hit_counter_saturates() {
+        let words = (0..600).map(|i| word(0, 0, i)).collect();
+        let chunk = Chunk::new(words, 0);
+        assert_eq!(chunk.frame_word.hit_counter, 0x1FF);
+        assert_eq!(check_hit_counter(&chunk), None);
+    }
+
+    #[test]
+    fn frame_counters_must_be_consecutive() {
+        let previous = Chunk::new(vec![], 10);
+        let chunk = Chunk::new(vec![], 12);
+        assert_eq!(
+            validate_chunk(&chunk, Some(&previous)),
+            vec![Issue::FrameGap {
+                previous: 10,
+                frame_counter: 12
+            }]
+        );
+
+        // Continuing across the 28 bit wrap is fine
+        let previous = Chunk::new(vec![], (1 << 28) - 1);
+        let chunk = Chunk::new(vec![], 0);
+        assert_eq!(validate_chunk(&chunk, Some(&previous)), vec![]);
+    }
+
+    #[test]
+    fn words_are_checked_one_by_one() {
+        let mut unmapped = word(0, 0, 3);
+        unmapped.address = MAPPED_COLUMNS * HA_PR_COL;
+        let mut empty = word(0, 0, 4);
+        empty.address_arbiter = 0;
+        let early = word(0, 0, 1);
+        let chunk = Chunk::new(vec![word(0, 0, 2), unmapped, empty, early], 0);
+
+        assert_eq!(
+            validate_chunk(&chunk, None),
+            vec![
+                Issue::UnmappedAddress {
+                    word_idx: 1,
+                    address: MAPPED_COLUMNS * HA_PR_COL
+                },
+                Issue::EmptyArbiter { word_idx: 2 },
+                Issue::OutOfOrder { word_idx: 3 },
+            ]
+        );
+    }
+
+    #[test]
+    fn frame_words_among_the_data_words_are_flagged() {
+        let stray = DataWord::from(0b111 << 45 | 1 << 35);
+        let chunk = Chunk::new(vec![stray], 0);
+        assert_eq!(
+            validate_chunk(&chunk, None),
+            [Issue::StrayFrameWord { word_idx: 0 }]
+        );
+    }
+}
@@ -0,0 +1,201 @@
+use eframe::egui;
+use egui_file::FileDialog;
+
+use crate::tdcpix::{
+    pixel_to_address, write_tdcpix_txt, Chunk, DATA_WORD_FIELDS, HA_PR_COL, MAPPED_COLUMNS,
+    PIXELS_PR_ARBITER,
+};
+use crate::tdcpixapp::TDCpixApp;
+use crate::widgets::inspector_window::bit_diagram;
+use crate::widgets::timeline::format_time_precise;
+
+pub struct BuilderWindow<'a> {
+    main_app: &'a mut TDCpixApp,
+}
+
+impl<'a> BuilderWindow<'a> {
+    pub fn new(main_app: &'a mut TDCpixApp) -> Self {
+        BuilderWindow { main_app }
+    }
+
+    pub fn show(mut self, ctx: &egui::Context) {
+        let mut open = self.main_app.show_builder;
+        egui::Window::new("Word builder")
+            .open(&mut open)
+            .default_width(520.0)
+            .show(ctx, |ui| self.contents(ui));
+        self.main_app.show_builder &= open;
+    }
+
+    fn contents(&mut self, ui: &mut egui::Ui) {
+        let app = &mut *self.main_app;
+        let dw = &mut app.builder_word;
+
+        egui::Grid::new("builder_grid").show(ui, |ui| {
+            ui.label("Pixel:");
+            ui.horizontal(|ui| {
+                // Not clamped, egui reports clamping as a change, which would
+                // overwrite unmapped addresses and arbiter patterns set below
+                let pixel = dw.pixel();
+                let (mut x, mut y) = pixel;
+                ui.add(egui::DragValue::new(&mut x).prefix("x "));
+                ui.add(egui::DragValue::new(&mut y).prefix("y "));
+                if (x, y) != pixel {
+                    (dw.address, dw.address_arbiter) = pixel_to_address(
+                        x.min(MAPPED_COLUMNS - 1),
+                        y.min(HA_PR_COL * PIXELS_PR_ARBITER - 1),
+                    );
+                }
+            });
+            ui.end_row();
+
+            ui.label("Address:");
+            ui.add(egui::DragValue::new(&mut dw.address).clamp_range(0..=0x7F));
+            ui.end_row();
+
+            ui.label("Arbiter:");
+            bit_checkboxes(ui, &mut dw.address_arbiter);
+            ui.end_row();
+
+            ui.label("Pileup:");
+            bit_checkboxes(ui, &mut dw.address_pileup);
+            ui.end_row();
+
+            ui.label("Leading coarse:");
+            ui.horizontal(|ui| {
+                ui.add(egui::DragValue::new(&mut dw.leading_coarse_time).clamp_range(0..=0xFFF));
+                selector_checkbox(ui, &mut dw.leading_coarse_time_selector);
+            });
+            ui.end_row();
+
+            ui.label("Leading fine:");
+            ui.add(egui::DragValue::new(&mut dw.leading_fine_time).clamp_range(0..=0x1F));
+            ui.end_row();
+
+            ui.label("Trailing coarse:");
+            ui.horizontal(|ui| {
+                ui.add(egui::DragValue::new(&mut dw.trailing_coarse_time).clamp_range(0..=0x3F));
+                selector_checkbox(ui, &mut dw.trailing_coarse_time_selector);
+            });
+            ui.end_row();
+
+            ui.label("Trailing fine:");
+            ui.add(egui::DragValue::new(&mut dw.trailing_fine_time).clamp_range(0..=0x1F));
+            ui.end_row();
+        });
+        dw.data_selector = 1;
+        dw.raw = dw.encode();
+
+        ui.horizontal(|ui| {
+            ui.monospace(format!("0x{:012X}", dw.raw));
+            if ui.small_button("Copy").clicked() {
+                ui.output_mut(|o| o.copied_text = format!("{:012x}", dw.raw));
+            }
+            ui.label(format!(
+                "start {}, duration {}",
+                format_time_precise(dw.get_start_time() as f64),
+                format_time_precise(dw.get_duration() as f64)
+            ));
+        });
+        bit_diagram(ui, dw.raw, &DATA_WORD_FIELDS);
+
+        if ui.button("Add to chunk").clicked() {
+            app.builder_words.push(app.builder_word);
+        }
+
+        ui.separator();
+        ui.horizontal(|ui| {
+            ui.label(format!("Chunk: {} words, frame", app.builder_words.len()));
+            ui.add(egui::DragValue::new(&mut app.builder_frame_counter).clamp_range(0..=0xFFFFFFF));
+        });
+
+        let mut remove = None;
+        egui::ScrollArea::vertical()
+            .max_height(150.0)
+            .show(ui, |ui| {
+                for (i, word) in app.builder_words.iter().enumerate() {
+                    ui.horizontal(|ui| {
+                        ui.monospace(format!("{:3} {:012x}", i, word.raw));
+                        ui.label(format!("{:?}", word.pixel()));
+                        if ui.small_button("Edit").clicked() {
+                            app.builder_word = *word;
+                        }
+                        if ui.small_button("✖").clicked() {
+                            remove = Some(i);
+                        }
+                    });
+                }
+            });
+        if let Some(i) = remove {
+            app.builder_words.remove(i);
+        }
+
+        let chunk = Chunk::new(app.builder_words.clone(), app.builder_frame_counter);
+        ui.monospace(format!("frame word {:012x}", chunk.frame_word.raw));
+        ui.horizontal(|ui| {
+            if ui.button("Clear").clicked() {
+                app.builder_words.clear();
+            }
+            if ui.button("View").clicked() {
+                app.set_chunks(vec![chunk.clone()], None);
+            }
+            if ui.button("Save as...").clicked() {
+                let mut dialog = FileDialog::save_file(app.file_path.clone());
+                dialog.open();
+                app.builder_save_dialog = Some(dialog);
+            }
+        });
+
+        if let Some(dialog) = &mut app.builder_save_dialog {
+            if dialog.show(ui.ctx()).selected() {
+                if let Some(file) = dialog.path() {
+                    if let Err(e) = write_tdcpix_txt(&file, &[chunk]) {
+                        eprintln!("Could not write {}: {}", file.display(), e);
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// One checkbox per pixel of an arbiter pattern, first pixel on the left.
+fn bit_checkboxes(ui: &mut egui::Ui, bits: &mut u8) {
+    ui.horizontal(|ui| {
+        for p in 0..PIXELS_PR_ARBITER {
+            let mut set = *bits & (1 << p) != 0;
+            if ui.checkbox(&mut set, "").changed() {
+                *bits ^= 1 << p;
+            }
+        }
+    });
+}
+
+fn selector_checkbox(ui: &mut egui::Ui, selector: &mut u8) {
+    let mut set = *selector != 0;
+    if ui.checkbox(&mut set, "selector").changed() {
+        *selector = set as u8;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn typed_address_and_arbiter_survive_repaints() {
+        let mut app = TDCpixApp::default();
+        app.show_builder = true;
+        // Outside the mapped columns, with more than one arbiter bit set
+        app.builder_word.address = 0x7F;
+        app.builder_word.address_arbiter = 0b10101;
+
+        let ctx = egui::Context::default();
+        for _ in 0..3 {
+            let _ = ctx.run(egui::RawInput::default(), |ctx| {
+                BuilderWindow::new(&mut app).show(ctx)
+            });
+        }
+        assert_eq!(app.builder_word.address, 0x7F);
+        assert_eq!(app.builder_word.address_arbiter, 0b10101);
+    }
+}
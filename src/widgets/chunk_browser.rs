@@ -0,0 +1,304 @@
+use eframe::egui;
+use egui_extras::{Column, TableBuilder};
+
+use crate::tdcpixapp::TDCpixApp;
+use crate::validation::ChunkSummary;
+
+/// Columns of the chunk browser, each can be sorted by.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ChunkColumn {
+    Index,
+    Frame,
+    Words,
+    HitCounter,
+    Collisions,
+    Pileup,
+    Status,
+}
+
+impl ChunkColumn {
+    pub const ALL: [ChunkColumn; 7] = [
+        ChunkColumn::Index,
+        ChunkColumn::Frame,
+        ChunkColumn::Words,
+        ChunkColumn::HitCounter,
+        ChunkColumn::Collisions,
+        ChunkColumn::Pileup,
+        ChunkColumn::Status,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            ChunkColumn::Index => "#",
+            ChunkColumn::Frame => "Frame",
+            ChunkColumn::Words => "Words",
+            ChunkColumn::HitCounter => "hit_counter",
+            ChunkColumn::Collisions => "Collisions",
+            ChunkColumn::Pileup => "Pileup",
+            ChunkColumn::Status => "Status",
+        }
+    }
+
+    fn sort_key(&self, idx: usize, summary: &ChunkSummary) -> u64 {
+        match self {
+            ChunkColumn::Index => idx as u64,
+            ChunkColumn::Frame => summary.frame_counter as u64,
+            ChunkColumn::Words => summary.words as u64,
+            ChunkColumn::HitCounter => summary.hit_counter as u64,
+            ChunkColumn::Collisions => summary.collisions as u64,
+            ChunkColumn::Pileup => summary.pileup_words as u64,
+            ChunkColumn::Status => summary.issues.len() as u64,
+        }
+    }
+
+    fn text(&self, idx: usize, summary: &ChunkSummary) -> String {
+        match self {
+            ChunkColumn::Index => idx.to_string(),
+            ChunkColumn::Frame => summary.frame_counter.to_string(),
+            ChunkColumn::Words => summary.words.to_string(),
+            ChunkColumn::HitCounter => summary.hit_counter.to_string(),
+            ChunkColumn::Collisions => summary.collisions.to_string(),
+            ChunkColumn::Pileup => summary.pileup_words.to_string(),
+            ChunkColumn::Status => match summary.issues.len() {
+                0 => "ok".to_string(),
+                1 => "1 issue".to_string(),
+                n => format!("{} issues", n),
+            },
+        }
+    }
+}
+
+/// Which chunks the browser lists.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct ChunkFilter {
+    pub with_pileup: bool,
+    pub with_issues: bool,
+    pub non_empty: bool,
+}
+
+impl ChunkFilter {
+    pub fn matches(&self, summary: &ChunkSummary) -> bool {
+        (!self.with_pileup || summary.pileup_words > 0)
+            && (!self.with_issues || !summary.issues.is_empty())
+            && (!self.non_empty || summary.words > 0)
+    }
+}
+
+/// Indices of the listed chunks in the order shown, kept until the chunks,
+/// sorting or filter change.
+#[derive(Default)]
+pub struct ChunkOrder {
+    // (chunks_generation, number of chunks, sorting, filter) the order was made for
+    key: Option<(u64, usize, (ChunkColumn, bool), ChunkFilter)>,
+    pub rows: Vec<usize>,
+}
+
+impl ChunkOrder {
+    fn new(
+        chunks_generation: u64,
+        summaries: &[ChunkSummary],
+        (sort_column, ascending): (ChunkColumn, bool),
+        filter: ChunkFilter,
+    ) -> Self {
+        let mut rows: Vec<usize> = (0..summaries.len())
+            .filter(|&i| filter.matches(&summaries[i]))
+            .collect();
+        rows.sort_by_key(|&i| sort_column.sort_key(i, &summaries[i]));
+        if !ascending {
+            rows.reverse();
+        }
+        ChunkOrder {
+            key: Some((
+                chunks_generation,
+                summaries.len(),
+                (sort_column, ascending),
+                filter,
+            )),
+            rows,
+        }
+    }
+
+    fn is_for(
+        &self,
+        chunks_generation: u64,
+        summaries: &[ChunkSummary],
+        sort: (ChunkColumn, bool),
+        filter: ChunkFilter,
+    ) -> bool {
+        self.key == Some((chunks_generation, summaries.len(), sort, filter))
+    }
+}
+
+pub struct ChunkBrowser<'a> {
+    main_app: &'a mut TDCpixApp,
+}
+
+impl<'a> ChunkBrowser<'a> {
+    pub fn new(main_app: &'a mut TDCpixApp) -> Self {
+        ChunkBrowser { main_app }
+    }
+
+    pub fn show(mut self, ctx: &egui::Context) {
+        let mut open = self.main_app.show_chunk_browser;
+        egui::Window::new("Chunks")
+            .open(&mut open)
+            .default_size(egui::vec2(560.0, 360.0))
+            .show(ctx, |ui| self.contents(ui));
+        self.main_app.show_chunk_browser &= open;
+    }
+
+    fn contents(&mut self, ui: &mut egui::Ui) {
+        let app = &mut *self.main_app;
+        app.update_chunk_summaries();
+
+        ui.horizontal(|ui| {
+            ui.label("Only:");
+            ui.checkbox(&mut app.chunk_filter.with_pileup, "with pileup");
+            ui.checkbox(&mut app.chunk_filter.with_issues, "with issues");
+            ui.checkbox(&mut app.chunk_filter.non_empty, "non-empty");
+        });
+
+        let summaries = &app.chunk_summaries;
+        let (sort_column, ascending) = app.chunk_browser_sort;
+        if !app.chunk_order.is_for(
+            app.chunks_generation,
+            summaries,
+            app.chunk_browser_sort,
+            app.chunk_filter,
+        ) {
+            app.chunk_order = ChunkOrder::new(
+                app.chunks_generation,
+                summaries,
+                app.chunk_browser_sort,
+                app.chunk_filter,
+            );
+        }
+        let order = &app.chunk_order.rows;
+        ui.label(format!("{} of {} chunks", order.len(), summaries.len()));
+
+        let mut clicked = None;
+        let mut sort_clicked = None;
+        TableBuilder::new(ui)
+            .striped(true)
+            .columns(Column::auto().at_least(40.0), ChunkColumn::ALL.len())
+            .header(20.0, |mut header| {
+                for column in ChunkColumn::ALL {
+                    header.col(|ui| {
+                        let arrow = match (column == sort_column, ascending) {
+                            (false, _) => "",
+                            (true, true) => " ⏶",
+                            (true, false) => " ⏷",
+                        };
+                        let label = format!("{}{}", column.name(), arrow);
+                        if ui.button(egui::RichText::new(label).strong()).clicked() {
+                            sort_clicked = Some(column);
+                        }
+                    });
+                }
+            })
+            .body(|body| {
+                body.rows(18.0, order.len(), |row_idx, mut row| {
+                    let chunk_idx = order[row_idx];
+                    let summary = &summaries[chunk_idx];
+                    let selected = chunk_idx == app.analysis_chunk_idx;
+                    for column in ChunkColumn::ALL {
+                        row.col(|ui| {
+                            let mut text =
+                                egui::RichText::new(column.text(chunk_idx, summary)).monospace();
+                            if column == ChunkColumn::Status && !summary.issues.is_empty() {
+                                text = text.color(ui.visuals().warn_fg_color);
+                            }
+                            let mut response = ui.selectable_label(selected, text);
+                            if column == ChunkColumn::Status && !summary.issues.is_empty() {
+                                let issues: Vec<String> =
+                                    summary.issues.iter().map(|i| i.to_string()).collect();
+                                response = response.on_hover_text(issues.join("\n"));
+                            }
+                            if response.clicked() {
+                                clicked = Some(chunk_idx);
+                            }
+                        });
+                    }
+                });
+            });
+
+        if let Some(column) = sort_clicked {
+            app.chunk_browser_sort = if column == sort_column {
+                (column, !ascending)
+            } else {
+                (column, true)
+            };
+        }
+        if let Some(chunk_idx) = clicked {
+            app.update_analysis_chunk_idx(chunk_idx);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::validation::Issue;
+
+    fn summary(words: usize, pileup_words: usize, issues: Vec<Issue>) -> ChunkSummary {
+        ChunkSummary {
+            frame_counter: 0,
+            words,
+            hit_counter: words as u16,
+            collisions: 0,
+            pileup_words,
+            issues,
+        }
+    }
+
+    #[test]
+    fn order_is_sorted_filtered_and_kept_until_the_chunks_change() {
+        let summaries = [
+            summary(2, 0, vec![]),
+            summary(0, 0, vec![]),
+            summary(5, 1, vec![]),
+        ];
+        let filter = ChunkFilter {
+            non_empty: true,
+            ..Default::default()
+        };
+        let sort = (ChunkColumn::Words, false);
+        let order = ChunkOrder::new(1, &summaries, sort, filter);
+        assert_eq!(order.rows, [2, 0]);
+        assert!(order.is_for(1, &summaries, sort, filter));
+        assert!(!order.is_for(1, &summaries[..2], sort, filter));
+        assert!(!order.is_for(2, &summaries, sort, filter));
+        assert!(!order.is_for(1, &summaries, (ChunkColumn::Words, true), filter));
+        assert!(!order.is_for(1, &summaries, sort, ChunkFilter::default()));
+    }
+
+    #[test]
+    fn default_filter_matches_everything() {
+        let filter = ChunkFilter::default();
+        assert!(filter.matches(&summary(0, 0, vec![])));
+        assert!(filter.matches(&summary(3, 1, vec![])));
+    }
+
+    #[test]
+    fn filters_combine() {
+        let gap = Issue::FrameGap {
+            previous: 0,
+            frame_counter: 2,
+        };
+        let filter = ChunkFilter {
+            with_pileup: true,
+            with_issues: true,
+            non_empty: false,
+        };
+        assert!(filter.matches(&summary(2, 1, vec![gap.clone()])));
+        assert!(!filter.matches(&summary(2, 1, vec![])));
+        assert!(!filter.matches(&summary(2, 0, vec![gap])));
+
+        let non_empty = ChunkFilter {
+            non_empty: true,
+            ..Default::default()
+        };
+        assert!(non_empty.matches(&summary(1, 0, vec![])));
+        assert!(!non_empty.matches(&summary(0, 0, vec![])));
+    }
+}
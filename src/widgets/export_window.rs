@@ -0,0 +1,299 @@
+use eframe::egui;
+use egui_file::FileDialog;
+
+use super::pixelgrid::GridContent;
+use crate::export::{
+    export_hits, export_view, AnimationExport, AnimationFormat, HitSelection, HitsFormat,
+    ViewFormat,
+};
+use crate::tdcpixapp::TDCpixApp;
+
+/// Which of the decoded words the export window writes, all of them by default.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct HitExportOptions {
+    pub current_chunk_only: bool,
+    pub selected_pixel_only: bool,
+    // Only words starting in the window brushed on the timeline
+    pub time_window_only: bool,
+}
+
+impl HitExportOptions {
+    pub fn selection(&self, app: &TDCpixApp) -> HitSelection {
+        let idx = app.analysis_chunk_idx;
+        HitSelection {
+            chunks: self.current_chunk_only.then_some((idx, idx)),
+            pixel: app
+                .selection
+                .filter(|_| self.selected_pixel_only)
+                .map(|s| s.pixel),
+            time_window: app.time_filter.filter(|_| self.time_window_only),
+        }
+    }
+}
+
+pub struct ExportWindow<'a> {
+    main_app: &'a mut TDCpixApp,
+}
+
+impl<'a> ExportWindow<'a> {
+    pub fn new(main_app: &'a mut TDCpixApp) -> Self {
+        ExportWindow { main_app }
+    }
+
+    pub fn show(mut self, ctx: &egui::Context) {
+        let mut open = self.main_app.show_export;
+        egui::Window::new("Export")
+            .open(&mut open)
+            .resizable(false)
+            .show(ctx, |ui| self.contents(ui));
+        self.main_app.show_export &= open;
+    }
+
+    /// The exported images look like the window does now.
+    fn sync_settings(app: &mut TDCpixApp) {
+        let settings = &mut app.export_settings;
+        settings.color_mode = app.color_mode;
+        settings.grouping = app.timeline_grouping;
+        settings.timeline_chunks = app.timeline_chunks;
+        settings.timeline_view = app.timeline_view;
+        settings.calibration = app.calibration.clone();
+        settings.file_name = app
+            .file_path
+            .as_ref()
+            .and_then(|p| p.file_name())
+            .map(|n| n.to_string_lossy().into_owned());
+        if app.animation_config.afterglow_half_life.is_some() {
+            app.animation_config.afterglow_half_life = Some(app.afterglow_half_life);
+        }
+    }
+
+    fn contents(&mut self, ui: &mut egui::Ui) {
+        let app = &mut *self.main_app;
+        let has_chunks = !app.chunks.is_empty();
+
+        egui::Grid::new("export_image_grid").show(ui, |ui| {
+            let settings = &mut app.export_settings;
+            ui.label("Width:");
+            ui.add(
+                egui::DragValue::new(&mut settings.width)
+                    .clamp_range(200..=4000)
+                    .suffix(" px"),
+            );
+            ui.end_row();
+
+            ui.label("Timeline:");
+            ui.checkbox(&mut settings.include_timeline, "")
+                .on_hover_text("Uses the lanes, chunks and zoom of the timeline");
+            ui.end_row();
+        });
+
+        ui.separator();
+        ui.horizontal(|ui| {
+            ui.label("Current view:");
+            for format in [ViewFormat::Png, ViewFormat::Svg] {
+                let label = format!("{}...", format.extension().to_uppercase());
+                if ui
+                    .add_enabled(has_chunks, egui::Button::new(label))
+                    .clicked()
+                {
+                    let mut dialog = FileDialog::save_file(app.file_path.clone());
+                    dialog.open();
+                    app.view_save_dialog = Some((dialog, format));
+                }
+            }
+        });
+
+        ui.separator();
+        self.animation_section(ui);
+        ui.separator();
+        self.hits_section(ui);
+
+        let app = &mut *self.main_app;
+        if let Some(status) = &app.export_status {
+            ui.label(status);
+        }
+
+        let mut view_file = None;
+        if let Some((dialog, format)) = &mut app.view_save_dialog {
+            if dialog.show(ui.ctx()).selected() {
+                if let Some(mut file) = dialog.path() {
+                    if file.extension().is_none() {
+                        file.set_extension(format.extension());
+                    }
+                    view_file = Some((file, *format));
+                }
+            }
+        }
+        if let Some((file, format)) = view_file {
+            Self::sync_settings(app);
+            let result = export_view(
+                &app.export_settings,
+                &app.chunks,
+                app.analysis_chunk_idx,
+                &GridContent::from_app(app),
+                format,
+                &file,
+            );
+            app.export_status = Some(match result {
+                Ok(()) => format!("Wrote {}", file.display()),
+                Err(e) => e,
+            });
+        }
+    }
+
+    fn animation_section(&mut self, ui: &mut egui::Ui) {
+        let app = &mut *self.main_app;
+        let last_idx = app.chunks.len().saturating_sub(1);
+        let config = &mut app.animation_config;
+
+        ui.label("Animation:");
+        egui::Grid::new("export_animation_grid").show(ui, |ui| {
+            ui.label("Chunks:");
+            ui.horizontal(|ui| {
+                ui.add(egui::DragValue::new(&mut config.first).clamp_range(0..=last_idx));
+                ui.label("to");
+                ui.add(egui::DragValue::new(&mut config.last).clamp_range(0..=last_idx));
+                if ui
+                    .button("From here")
+                    .on_hover_text("Start at the chunk shown")
+                    .clicked()
+                {
+                    config.first = app.analysis_chunk_idx;
+                    config.last = config.last.max(config.first);
+                }
+                if ui.button("All").clicked() {
+                    config.first = 0;
+                    config.last = last_idx;
+                }
+            });
+            ui.end_row();
+
+            ui.label("Frame rate:");
+            ui.add(
+                egui::DragValue::new(&mut config.fps)
+                    .speed(0.1)
+                    .clamp_range(0.1..=60.0)
+                    .suffix(" fps"),
+            );
+            ui.end_row();
+
+            ui.label("Afterglow:");
+            let mut afterglow = config.afterglow_half_life.is_some();
+            if ui
+                .checkbox(&mut afterglow, "")
+                .on_hover_text("Uses the half-life of the playback")
+                .changed()
+            {
+                config.afterglow_half_life = afterglow.then_some(app.afterglow_half_life);
+            }
+            ui.end_row();
+
+            ui.label("Format:");
+            ui.horizontal(|ui| {
+                ui.radio_value(&mut config.format, AnimationFormat::Gif, "GIF");
+                ui.radio_value(
+                    &mut config.format,
+                    AnimationFormat::PngSequence,
+                    "PNG sequence",
+                );
+            });
+            ui.end_row();
+        });
+
+        match &app.animation_export {
+            Some(export) => {
+                let done = export.progress();
+                ui.add(
+                    egui::ProgressBar::new(done as f32 / export.frames as f32)
+                        .text(format!("Frame {} of {}", done, export.frames)),
+                );
+            }
+            None => {
+                if ui
+                    .add_enabled(!app.chunks.is_empty(), egui::Button::new("Export..."))
+                    .clicked()
+                {
+                    let mut dialog = FileDialog::save_file(app.file_path.clone());
+                    dialog.open();
+                    app.animation_save_dialog = Some(dialog);
+                }
+            }
+        }
+
+        let mut animation_file = None;
+        if let Some(dialog) = &mut app.animation_save_dialog {
+            if dialog.show(ui.ctx()).selected() {
+                animation_file = dialog.path();
+            }
+        }
+        if let Some(file) = animation_file {
+            Self::sync_settings(app);
+            // The result is picked up by `TDCpixApp::update`
+            match AnimationExport::start(
+                &app.chunks,
+                &app.export_settings,
+                &app.animation_config,
+                file,
+            ) {
+                Ok(export) => {
+                    app.animation_export = Some(export);
+                    app.export_status = None;
+                }
+                Err(e) => app.export_status = Some(e),
+            }
+        }
+    }
+
+    fn hits_section(&mut self, ui: &mut egui::Ui) {
+        let app = &mut *self.main_app;
+        let options = &mut app.hit_export_options;
+
+        ui.label("Decoded words:");
+        ui.horizontal(|ui| {
+            ui.checkbox(&mut options.current_chunk_only, "current chunk");
+            ui.add_enabled(
+                app.selection.is_some(),
+                egui::Checkbox::new(&mut options.selected_pixel_only, "selected pixel"),
+            );
+            ui.add_enabled(
+                app.time_filter.is_some(),
+                egui::Checkbox::new(&mut options.time_window_only, "time window"),
+            )
+            .on_hover_text("Only words starting in the window brushed on the timeline");
+        });
+        ui.horizontal(|ui| {
+            for (format, label) in [
+                (HitsFormat::Csv, "CSV..."),
+                (HitsFormat::JsonLines, "JSON Lines..."),
+            ] {
+                if ui
+                    .add_enabled(!app.chunks.is_empty(), egui::Button::new(label))
+                    .clicked()
+                {
+                    let mut dialog = FileDialog::save_file(app.file_path.clone());
+                    dialog.open();
+                    app.hits_save_dialog = Some((dialog, format));
+                }
+            }
+        });
+
+        let mut hits_file = None;
+        if let Some((dialog, format)) = &mut app.hits_save_dialog {
+            if dialog.show(ui.ctx()).selected() {
+                if let Some(mut file) = dialog.path() {
+                    if file.extension().is_none() {
+                        file.set_extension(format.extension());
+                    }
+                    hits_file = Some((file, *format));
+                }
+            }
+        }
+        if let Some((file, format)) = hits_file {
+            let selection = app.hit_export_options.selection(app);
+            app.export_status = Some(match export_hits(&app.chunks, &selection, format, &file) {
+                Ok(words) => format!("Wrote {} words", words),
+                Err(e) => e,
+            });
+        }
+    }
+}
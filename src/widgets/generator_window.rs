@@ -0,0 +1,196 @@
+use eframe::egui;
+use egui_file::FileDialog;
+
+use crate::generator::{generate, parse_pixel, BeamProfile, TestPulse};
+use crate::net::{write_chunks, WireFormat};
+use crate::tdcpixapp::TDCpixApp;
+
+pub struct GeneratorWindow<'a> {
+    main_app: &'a mut TDCpixApp,
+}
+
+impl<'a> GeneratorWindow<'a> {
+    pub fn new(main_app: &'a mut TDCpixApp) -> Self {
+        GeneratorWindow { main_app }
+    }
+
+    pub fn show(mut self, ctx: &egui::Context) {
+        let mut open = self.main_app.show_generator;
+        egui::Window::new("Generate data")
+            .open(&mut open)
+            .resizable(false)
+            .show(ctx, |ui| self.contents(ui));
+        self.main_app.show_generator &= open;
+    }
+
+    fn contents(&mut self, ui: &mut egui::Ui) {
+        let app = &mut *self.main_app;
+        let config = &mut app.generator_config;
+
+        egui::Grid::new("generator_grid").show(ui, |ui| {
+            ui.label("Seed:");
+            ui.add(egui::DragValue::new(&mut config.seed));
+            ui.end_row();
+
+            ui.label("Chunks:");
+            ui.add(egui::DragValue::new(&mut config.num_chunks).clamp_range(1..=1_000_000));
+            ui.end_row();
+
+            ui.label("First frame:");
+            ui.add(egui::DragValue::new(&mut config.first_frame));
+            ui.end_row();
+
+            ui.label("Hits / chunk:");
+            ui.add(
+                egui::DragValue::new(&mut config.hits_per_chunk)
+                    .speed(0.1)
+                    .clamp_range(0.0..=200.0),
+            );
+            ui.end_row();
+
+            ui.label("Noise / chunk:");
+            ui.add(
+                egui::DragValue::new(&mut config.noise_per_chunk)
+                    .speed(0.1)
+                    .clamp_range(0.0..=200.0),
+            );
+            ui.end_row();
+
+            ui.label("Pileup chance:");
+            ui.add(
+                egui::DragValue::new(&mut config.pileup_probability)
+                    .speed(0.001)
+                    .clamp_range(0.0..=1.0),
+            );
+            ui.end_row();
+
+            ui.label("ToT mean / sigma (ps):");
+            ui.horizontal(|ui| {
+                ui.add(egui::DragValue::new(&mut config.tot_mean_ps).speed(100.0));
+                ui.add(egui::DragValue::new(&mut config.tot_sigma_ps).speed(100.0));
+            });
+            ui.end_row();
+
+            ui.label("Beam:");
+            ui.horizontal(|ui| {
+                let is_uniform = config.beam == BeamProfile::Uniform;
+                if ui.selectable_label(is_uniform, "Uniform").clicked() {
+                    config.beam = BeamProfile::Uniform;
+                }
+                if ui.selectable_label(!is_uniform, "Gaussian").clicked() && is_uniform {
+                    config.beam = BeamProfile::Gaussian {
+                        x: 5.0,
+                        y: 22.0,
+                        sigma_x: 2.0,
+                        sigma_y: 6.0,
+                    };
+                }
+                if let BeamProfile::Gaussian {
+                    x,
+                    y,
+                    sigma_x,
+                    sigma_y,
+                } = &mut config.beam
+                {
+                    ui.add(egui::DragValue::new(x).speed(0.1).prefix("x "));
+                    ui.add(egui::DragValue::new(y).speed(0.1).prefix("y "));
+                    ui.add(egui::DragValue::new(sigma_x).speed(0.1).prefix("σx "));
+                    ui.add(egui::DragValue::new(sigma_y).speed(0.1).prefix("σy "));
+                }
+            });
+            ui.end_row();
+
+            ui.label("Test pulse:");
+            ui.horizontal(|ui| {
+                egui::ComboBox::from_id_source("test_pulse_combo")
+                    .selected_text(format!("{:?}", config.test_pulse))
+                    .show_ui(ui, |ui| {
+                        for pulse in [
+                            TestPulse::Off,
+                            TestPulse::Column(0),
+                            TestPulse::Row(0),
+                            TestPulse::Diagonal,
+                            TestPulse::Checkerboard,
+                        ] {
+                            ui.selectable_value(
+                                &mut config.test_pulse,
+                                pulse,
+                                format!("{:?}", pulse),
+                            );
+                        }
+                    });
+                match &mut config.test_pulse {
+                    TestPulse::Column(idx) | TestPulse::Row(idx) => {
+                        ui.add(egui::DragValue::new(idx));
+                    }
+                    _ => (),
+                }
+                ui.add(
+                    egui::DragValue::new(&mut config.test_pulse_period)
+                        .clamp_range(1..=usize::MAX)
+                        .prefix("every "),
+                );
+            });
+            ui.end_row();
+
+            ui.label("Dead pixels:");
+            if ui
+                .text_edit_singleline(&mut app.generator_dead_pixels)
+                .on_hover_text("x,y pairs separated by ';'")
+                .changed()
+            {
+                config.dead_pixels = app
+                    .generator_dead_pixels
+                    .split(';')
+                    .filter_map(|p| parse_pixel(p).ok())
+                    .collect();
+            }
+            ui.end_row();
+
+            ui.label("Save as:");
+            ui.horizontal(|ui| {
+                ui.radio_value(&mut app.generator_format, WireFormat::Hex, "Hex text");
+                ui.radio_value(&mut app.generator_format, WireFormat::Binary, "Binary")
+                    .on_hover_text("6 byte big endian words, as sent over the network");
+            });
+            ui.end_row();
+        });
+
+        ui.horizontal(|ui| {
+            if ui.button("Generate").clicked() {
+                let chunks = generate(&app.generator_config);
+                app.set_chunks(chunks, None);
+            }
+
+            if ui.button("Save as...").clicked() {
+                let mut dialog = FileDialog::save_file(app.file_path.clone());
+                dialog.open();
+                app.save_file_dialog = Some(dialog);
+            }
+        });
+
+        if let Some(dialog) = &mut app.save_file_dialog {
+            if dialog.show(ui.ctx()).selected() {
+                if let Some(file) = dialog.path() {
+                    let chunks = generate(&app.generator_config);
+                    match write_chunks(&file, &chunks, app.generator_format) {
+                        Ok(()) => {
+                            app.generator_error = None;
+                            // Only text files can be opened and followed
+                            let file = (app.generator_format == WireFormat::Hex).then_some(file);
+                            app.set_chunks(chunks, file);
+                        }
+                        Err(e) => {
+                            app.generator_error =
+                                Some(format!("Could not write {}: {}", file.display(), e));
+                        }
+                    }
+                }
+            }
+        }
+
+        if let Some(e) = &app.generator_error {
+            ui.colored_label(ui.visuals().error_fg_color, e);
+        }
+    }
+}
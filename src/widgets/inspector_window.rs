@@ -0,0 +1,199 @@
+use eframe::egui;
+
+use crate::calibration::CalibrationStatus;
+use crate::colors::category;
+use crate::tdcpix::{BitField, DATA_WORD_FIELDS, FRAME_PERIOD_PS, FRAME_WORD_FIELDS};
+use crate::tdcpixapp::TDCpixApp;
+use crate::validation::check_hit_counter;
+use crate::widgets::timeline::format_time_precise;
+
+pub struct InspectorWindow<'a> {
+    main_app: &'a mut TDCpixApp,
+}
+
+impl<'a> InspectorWindow<'a> {
+    pub fn new(main_app: &'a mut TDCpixApp) -> Self {
+        InspectorWindow { main_app }
+    }
+
+    pub fn show(mut self, ctx: &egui::Context) {
+        let mut open = self.main_app.show_inspector;
+        egui::Window::new("Word inspector")
+            .open(&mut open)
+            .default_width(520.0)
+            .show(ctx, |ui| self.contents(ui));
+        self.main_app.show_inspector &= open;
+    }
+
+    fn contents(&mut self, ui: &mut egui::Ui) {
+        let app = &*self.main_app;
+        // The selection may point past the end when live data dropped chunks
+        let Some((chunk_idx, word_idx, chunk)) = app
+            .selection
+            .and_then(|s| s.word)
+            .and_then(|(c, w)| app.chunks.get(c).map(|chunk| (c, w, chunk)))
+            .filter(|(_, w, chunk)| *w < chunk.data_words.len())
+        else {
+            ui.label("Click a pixel, timeline box or table row to inspect its word.");
+            return;
+        };
+        let dw = &chunk.data_words[word_idx];
+        let fw = &chunk.frame_word;
+
+        ui.heading(format!("Chunk {}, word {}", chunk_idx, word_idx));
+        ui.monospace(format!("0x{:012X}", dw.raw));
+        bit_diagram(ui, dw.raw, &DATA_WORD_FIELDS);
+        field_table(ui, "data_word_fields", dw.raw, &DATA_WORD_FIELDS);
+
+        ui.separator();
+        let start = dw.get_start_time();
+        let duration = dw.get_duration();
+        egui::Grid::new("derived_grid")
+            .striped(true)
+            .show(ui, |ui| {
+                ui.label("Leading time:");
+                ui.label(format_time_precise(start as f64));
+                ui.end_row();
+
+                ui.label("Time over threshold:");
+                ui.label(format_time_precise(duration as f64));
+                ui.end_row();
+
+                ui.label("Trailing time:");
+                ui.label(format_time_precise((start + duration) as f64));
+                ui.end_row();
+
+                ui.label("Time since frame 0:");
+                let absolute = fw.frame_counter as u64 * FRAME_PERIOD_PS + start;
+                ui.label(format!("{:.6} ms", absolute as f64 / 1e9));
+                ui.end_row();
+
+                ui.label("Hit pixels:");
+                ui.label(format_pixels(&dw.hit_pixels()));
+                ui.end_row();
+
+                ui.label("Pileup pixels:");
+                ui.label(format_pixels(&dw.pileup_pixels()));
+                ui.end_row();
+
+                ui.label("Calibration:");
+                ui.label(match app.calibration.status(dw.pixel()) {
+                    CalibrationStatus::Calibrated => "calibrated",
+                    CalibrationStatus::Masked => "masked",
+                    CalibrationStatus::Missing => "-",
+                });
+                ui.end_row();
+            });
+
+        ui.separator();
+        ui.label(egui::RichText::new("Frame word").strong());
+        ui.monospace(format!("0x{:012X}", fw.raw));
+        bit_diagram(ui, fw.raw, &FRAME_WORD_FIELDS);
+        field_table(ui, "frame_word_fields", fw.raw, &FRAME_WORD_FIELDS);
+        if let Some(issue) = check_hit_counter(chunk) {
+            ui.colored_label(ui.visuals().warn_fg_color, issue.to_string());
+        }
+    }
+}
+
+fn format_pixels(pixels: &[(u8, u8)]) -> String {
+    if pixels.is_empty() {
+        return "-".to_string();
+    }
+    pixels
+        .iter()
+        .map(|(x, y)| format!("({}, {})", x, y))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Draws the 48 bits of `raw`, most significant first, with each bit colored
+/// by the field it belongs to. Hovering a bit names its field.
+pub fn bit_diagram(ui: &mut egui::Ui, raw: u64, fields: &[BitField]) {
+    let cell_width = (ui.available_width() / 48.0).clamp(8.0, 16.0);
+    let cell_height = 18.0;
+    let (rect, response) = ui.allocate_exact_size(
+        egui::vec2(cell_width * 48.0, cell_height + 12.0),
+        egui::Sense::hover(),
+    );
+    let painter = ui.painter_at(rect);
+    let cell_rect = |bit: u8| {
+        egui::Rect::from_min_size(
+            rect.min + egui::vec2((47 - bit) as f32 * cell_width, 0.0),
+            egui::vec2(cell_width, cell_height),
+        )
+    };
+
+    for (i, field) in fields.iter().enumerate() {
+        let color = category(i);
+        for bit in field.low..=field.high {
+            let cell = cell_rect(bit);
+            painter.rect_filled(cell.shrink(0.5), 0.0, color.gamma_multiply(0.6));
+            painter.text(
+                cell.center(),
+                egui::Align2::CENTER_CENTER,
+                if raw >> bit & 1 == 1 { "1" } else { "0" },
+                egui::FontId::monospace(11.0),
+                egui::Color32::WHITE,
+            );
+        }
+        // Number the edges of each field below the bits
+        let mut edges = vec![field.high];
+        if field.low != field.high {
+            edges.push(field.low);
+        }
+        for bit in edges {
+            painter.text(
+                egui::pos2(cell_rect(bit).center().x, rect.bottom()),
+                egui::Align2::CENTER_BOTTOM,
+                bit.to_string(),
+                egui::FontId::proportional(8.0),
+                ui.visuals().weak_text_color(),
+            );
+        }
+    }
+
+    if let Some(pos) = response.hover_pos() {
+        let bit = 47
+            - ((pos.x - rect.left()) / cell_width)
+                .floor()
+                .clamp(0.0, 47.0) as u8;
+        if let Some(field) = fields.iter().find(|f| (f.low..=f.high).contains(&bit)) {
+            response.on_hover_text_at_pointer(format!("bit {}: {}", bit, field.name));
+        }
+    }
+}
+
+/// Lists each field of `raw` with its bits and value.
+pub fn field_table(ui: &mut egui::Ui, id: &str, raw: u64, fields: &[BitField]) {
+    egui::Grid::new(id).striped(true).show(ui, |ui| {
+        ui.label(egui::RichText::new("Field").strong());
+        ui.label(egui::RichText::new("Bits").strong());
+        ui.label(egui::RichText::new("Value").strong());
+        ui.label(egui::RichText::new("Binary").strong());
+        ui.end_row();
+
+        for (i, field) in fields.iter().enumerate() {
+            let value = field.value(raw);
+            ui.horizontal(|ui| {
+                let (swatch, _) =
+                    ui.allocate_exact_size(egui::vec2(10.0, 10.0), egui::Sense::hover());
+                ui.painter()
+                    .rect_filled(swatch, 0.0, category(i).gamma_multiply(0.6));
+                ui.label(field.name);
+            });
+            if field.high == field.low {
+                ui.label(field.high.to_string());
+            } else {
+                ui.label(format!("{}..{}", field.high, field.low));
+            }
+            ui.monospace(format!("{} (0x{:X})", value, value));
+            ui.monospace(format!(
+                "{:0width$b}",
+                value,
+                width = field.width() as usize
+            ));
+            ui.end_row();
+        }
+    });
+}
@@ -1,4 +1,11 @@
+pub mod builder_window;
+pub mod chunk_browser;
+pub mod export_window;
+pub mod generator_window;
+pub mod inspector_window;
+pub mod net_window;
 pub mod pixel;
 pub mod pixelgrid;
 pub mod timeline;
 pub mod utility_bar;
+pub mod word_table;
@@ -0,0 +1,92 @@
+use eframe::egui;
+
+use crate::net::{ConnectionStatus, Protocol, WireFormat};
+use crate::tdcpixapp::TDCpixApp;
+
+pub struct NetWindow<'a> {
+    main_app: &'a mut TDCpixApp,
+}
+
+impl<'a> NetWindow<'a> {
+    pub fn new(main_app: &'a mut TDCpixApp) -> Self {
+        NetWindow { main_app }
+    }
+
+    pub fn show(mut self, ctx: &egui::Context) {
+        let mut open = self.main_app.show_network;
+        egui::Window::new("Network input")
+            .open(&mut open)
+            .resizable(false)
+            .show(ctx, |ui| self.contents(ui));
+        self.main_app.show_network &= open;
+    }
+
+    fn contents(&mut self, ui: &mut egui::Ui) {
+        let app = &mut *self.main_app;
+        let running = app.net_source.is_some();
+        let config = &mut app.net_config;
+
+        // Settings can only be changed while stopped
+        ui.add_enabled_ui(!running, |ui| {
+            egui::Grid::new("net_grid").show(ui, |ui| {
+                ui.label("Protocol:");
+                ui.horizontal(|ui| {
+                    ui.radio_value(&mut config.protocol, Protocol::Tcp, "TCP");
+                    ui.radio_value(&mut config.protocol, Protocol::Udp, "UDP");
+                });
+                ui.end_row();
+
+                ui.label("Format:");
+                ui.horizontal(|ui| {
+                    ui.radio_value(&mut config.format, WireFormat::Hex, "Hex text");
+                    ui.radio_value(&mut config.format, WireFormat::Binary, "Binary");
+                });
+                ui.end_row();
+
+                ui.label("Address:");
+                ui.text_edit_singleline(&mut config.address)
+                    .on_hover_text("0.0.0.0 listens on all interfaces");
+                ui.end_row();
+
+                ui.label("Port:");
+                ui.add(egui::DragValue::new(&mut config.port));
+                ui.end_row();
+
+                ui.label("Keep chunks:");
+                ui.add(egui::DragValue::new(&mut config.capacity).clamp_range(1..=10_000_000));
+                ui.end_row();
+            });
+        });
+
+        if running {
+            if ui.button("Stop").clicked() {
+                app.stop_network();
+            }
+        } else if ui.button("Listen").clicked() {
+            app.start_network();
+        }
+
+        if let Some(net_source) = &app.net_source {
+            let (words, chunks) = net_source.totals();
+            match net_source.status() {
+                ConnectionStatus::Listening => ui.label("Waiting for a sender"),
+                ConnectionStatus::Connected(peer) => ui.label(format!("Receiving from {}", peer)),
+                ConnectionStatus::Error(e) => {
+                    ui.colored_label(egui::Color32::from_rgb(255, 0, 0), format!("Error: {}", e))
+                }
+            };
+            ui.label(format!(
+                "{:.0} words/s, {:.0} chunks/s",
+                net_source.word_rate, net_source.chunk_rate
+            ));
+            ui.label(format!("{} words, {} chunks in total", words, chunks));
+            let bad_lines = net_source.bad_lines();
+            if bad_lines > 0 {
+                ui.label(format!(
+                    "{} lines skipped, they could not be parsed",
+                    bad_lines
+                ));
+            }
+        }
+    }
+}
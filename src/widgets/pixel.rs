@@ -1,43 +1,31 @@
 use eframe::egui;
+
+use crate::colors::{HIT_COLOR, PILEUP_COLOR};
+use crate::hitmap::{HIT, PILEUP};
+
 pub enum HitType {
     Hit,
     Pileup,
     Other,
 }
 
-pub struct Pixel {
-    size: f32,
-    color: egui::Color32,
-    is_highlighted: bool,
-}
-
-impl Pixel {
-    pub fn new(size: f32, hit_type: HitType, is_highlighted: bool) -> Self {
-        Pixel {
-            size,
-            color: match hit_type {
-                HitType::Hit => egui::Color32::from_rgb(0, 255, 0),
-                HitType::Pileup => egui::Color32::from_rgb(255, 0, 0),
-                _ => egui::Color32::from_rgb(50, 50, 50),
-            },
-            is_highlighted,
+impl HitType {
+    pub fn from_flags(flags: u8) -> Self {
+        // A direct hit wins over pileup on the same pixel
+        if flags & HIT != 0 {
+            HitType::Hit
+        } else if flags & PILEUP != 0 {
+            HitType::Pileup
+        } else {
+            HitType::Other
         }
     }
-}
 
-impl egui::Widget for Pixel {
-    fn ui(self, ui: &mut egui::Ui) -> egui::Response {
-        let (rect, response) =
-            ui.allocate_exact_size(egui::Vec2::new(self.size, self.size), egui::Sense::click());
-        let painter = ui.painter();
-        painter.rect_filled(rect, 0.0, self.color);
-        if self.is_highlighted {
-            painter.rect_stroke(
-                rect,
-                0.0,
-                egui::Stroke::new(3.0, egui::Color32::from_rgb(255, 255, 255)),
-            );
+    pub fn color(&self) -> egui::Color32 {
+        match self {
+            HitType::Hit => HIT_COLOR,
+            HitType::Pileup => PILEUP_COLOR,
+            _ => egui::Color32::from_rgb(50, 50, 50),
         }
-        response
     }
 }
@@ -1,8 +1,154 @@
 use eframe::egui;
 
-use super::pixel::{HitType, Pixel};
+use super::pixel::HitType;
+use crate::calibration::Calibration;
+use crate::colors::{mix, ColorMode, HIT_COLOR};
+use crate::hitmap::{Afterglow, HitMap};
+use crate::tdcpix::Chunk;
 use crate::tdcpixapp::TDCpixApp;
 
+/// Where the pixels of a grid of `columns` x `rows` go inside a rect.
+pub struct GridGeometry {
+    pub columns: u8,
+    pub rows: u8,
+    // Top left corner and size of the whole grid
+    pub origin: egui::Pos2,
+    pub size: egui::Vec2,
+    // Pixel width and distance from one pixel to the next
+    pub pw: f32,
+    pub step: f32,
+}
+
+impl GridGeometry {
+    // Padding between the pixels as a fraction of the pixel width
+    const PP: f32 = 0.1;
+
+    /// The pixels stay square, so the grid is as large as the tighter of the
+    /// two dimensions of `rect` allows, and centered in it.
+    pub fn new(columns: u8, rows: u8, rect: egui::Rect) -> Self {
+        let pp = Self::PP;
+        let pw = f32::min(
+            rect.width() / ((columns as f32) + (columns as f32) * pp + pp),
+            rect.height() / ((rows as f32) + (rows as f32) * pp + pp),
+        );
+        let step = pw + pw * pp;
+        let size = egui::vec2(
+            columns as f32 * step + pw * pp,
+            rows as f32 * step + pw * pp,
+        );
+        let origin = egui::Align2::CENTER_CENTER
+            .align_size_within_rect(size, rect)
+            .min;
+        GridGeometry {
+            columns,
+            rows,
+            origin,
+            size,
+            pw,
+            step,
+        }
+    }
+
+    pub fn pixel_rect(&self, x: u8, y: u8) -> egui::Rect {
+        let pad = self.pw * Self::PP;
+        egui::Rect::from_min_size(
+            // index times width+padding + beginning padding
+            self.origin + egui::vec2(x as f32 * self.step + pad, y as f32 * self.step + pad),
+            egui::vec2(self.pw, self.pw),
+        )
+    }
+
+    /// Pixel under `pos`, None in the padding between pixels.
+    pub fn pixel_at(&self, pos: egui::Pos2) -> Option<(u8, u8)> {
+        let pad = self.pw * Self::PP;
+        let cell = (pos - self.origin - egui::vec2(pad, pad)) / self.step;
+        let (x, y) = (cell.x.floor(), cell.y.floor());
+        let on_pixel = x >= 0.0
+            && y >= 0.0
+            && x < self.columns as f32
+            && y < self.rows as f32
+            && self.pixel_rect(x as u8, y as u8).contains(pos);
+        on_pixel.then_some((x as u8, y as u8))
+    }
+
+    /// Lines between the quarterchips and between the pixel groups, with their width.
+    pub fn separators(&self) -> Vec<([egui::Pos2; 2], f32)> {
+        let mut lines = Vec::new();
+        for x in (10..self.columns).step_by(10) {
+            lines.push((
+                [
+                    self.origin + egui::vec2(x as f32 * self.step, 0.0),
+                    self.origin + egui::vec2(x as f32 * self.step, self.size.y),
+                ],
+                2.0,
+            ));
+        }
+        for y in (5..self.rows).step_by(5) {
+            lines.push((
+                [
+                    self.origin + egui::vec2(0.0, y as f32 * self.step),
+                    self.origin + egui::vec2(self.size.x, y as f32 * self.step),
+                ],
+                1.0,
+            ));
+        }
+        lines
+    }
+}
+
+pub const SEPARATOR_COLOR: egui::Color32 = egui::Color32::from_rgb(100, 100, 100);
+
+/// What is drawn in each pixel of the grid.
+pub struct GridContent<'a> {
+    pub chunk: Option<&'a Chunk>,
+    pub hit_map: &'a HitMap,
+    pub color_mode: ColorMode,
+    pub calibration: &'a Calibration,
+    // Hits starting outside this window are dimmed
+    pub time_filter: Option<(f64, f64)>,
+    pub afterglow: Option<&'a Afterglow>,
+}
+
+impl<'a> GridContent<'a> {
+    pub fn from_app(app: &'a TDCpixApp) -> Self {
+        GridContent {
+            chunk: app.chunks.get(app.analysis_chunk_idx),
+            hit_map: &app.hit_map,
+            color_mode: app.color_mode,
+            calibration: &app.calibration,
+            time_filter: app.time_filter,
+            afterglow: app.show_afterglow.then_some(&app.afterglow),
+        }
+    }
+
+    /// Hit pixels get the color of their word, the same as on the timeline,
+    /// and are dimmed when the word starts outside the time filter.
+    pub fn color(&self, x: u8, y: u8) -> egui::Color32 {
+        match (self.chunk, self.hit_map.word_at(x, y)) {
+            (Some(chunk), Some(word_idx)) => {
+                let dw = &chunk.data_words[word_idx];
+                let color = self.color_mode.color(dw, self.calibration);
+                match self.time_filter {
+                    Some((start, end))
+                        if !(start..=end).contains(&(dw.get_start_time() as f64)) =>
+                    {
+                        color.gamma_multiply(0.2)
+                    }
+                    _ => color,
+                }
+            }
+            _ => {
+                let color = HitType::from_flags(self.hit_map.get(x, y)).color();
+                // Pixels hit in the chunks played before fade out
+                match self.afterglow.map(|a| a.get(x, y)) {
+                    Some(glow) if glow > 0.0 => mix(color, HIT_COLOR, glow * 0.6),
+                    _ => color,
+                }
+            }
+        }
+    }
+}
+
 pub struct PixelGrid<'a> {
     w_pixels: u8,
     h_pixels: u8,
@@ -20,95 +166,70 @@ impl<'a> PixelGrid<'a> {
 }
 
 impl<'a> egui::Widget for PixelGrid<'a> {
-    fn ui(mut self, ui: &mut egui::Ui) -> egui::Response {
-        // pp = padding percentage, pw = pixel width
-        let pp = 0.1;
-        let pw =
-            self.main_app.w_dim.x / ((self.w_pixels as f32) + (self.w_pixels as f32) * pp + pp);
-
-        // Allocate widget for the whole pixel grid
-        let (_rect, response) = ui.allocate_exact_size(
-            egui::Vec2::new(
-                self.main_app.w_dim.x,
-                self.h_pixels as f32 * (pw + pw * pp) + pw * pp,
-            ),
-            egui::Sense::click(),
-        );
+    fn ui(self, ui: &mut egui::Ui) -> egui::Response {
+        // Allocate widget for the whole available space and center the grid in it
+        let (rect, response) = ui.allocate_exact_size(ui.available_size(), egui::Sense::click());
+        let grid = GridGeometry::new(self.w_pixels, self.h_pixels, rect);
 
-        // Draw the pixel grid
+        // All pixels go into a single mesh
+        let content = GridContent::from_app(self.main_app);
+        let mut mesh = egui::Mesh::default();
         for x in 0..self.w_pixels {
-            // q-chip lines
-            if x % 10 == 0 && x != 0 {
-                ui.painter().line_segment(
-                    [
-                        egui::pos2((x as f32) * (pw + pw * pp), 0.0),
-                        egui::pos2(
-                            (x as f32) * (pw + pw * pp),
-                            self.h_pixels as f32 * (pw + pw * pp) + pw * pp,
-                        ),
-                    ],
-                    egui::Stroke::new(2.0, egui::Color32::from_rgb(100, 100, 100)),
-                );
+            for y in 0..self.h_pixels {
+                mesh.add_colored_rect(grid.pixel_rect(x, y), content.color(x, y));
             }
+        }
+        let painter = ui.painter();
+        painter.add(egui::Shape::mesh(mesh));
 
-            // Pixels
-            for y in 0..self.h_pixels {
-                // Horizontal pixelgroup lines
-                if y % 5 == 0 && y != 0 {
-                    ui.painter().line_segment(
-                        [
-                            egui::pos2(0.0, (y as f32) * (pw + pw * pp)),
-                            egui::pos2(
-                                self.w_pixels as f32 * (pw + pw * pp) + pw * pp,
-                                (y as f32) * (pw + pw * pp),
-                            ),
-                        ],
-                        egui::Stroke::new(1.0, egui::Color32::from_rgb(100, 100, 100)),
-                    );
-                }
+        // q-chip lines and horizontal pixelgroup lines
+        for (points, width) in grid.separators() {
+            painter.line_segment(points, egui::Stroke::new(width, SEPARATOR_COLOR));
+        }
 
-                // Pixel object
-                let pixel = Pixel::new(
-                    pw,
-                    {
-                        match (
-                            self.main_app.hit_idxes.contains(&(x, y)),
-                            self.main_app.pileup_idxes.contains(&(x, y)),
-                        ) {
-                            // (true, true) => HitType::DoubleHit,
-                            (true, _) => HitType::Hit,
-                            (_, true) => HitType::Pileup,
-                            _ => HitType::Other,
-                        }
-                    },
-                    (x, y) == self.main_app.highlight_idx && self.main_app.has_selected_hit,
-                );
-
-                // Add pixel to UI
-                let px_response = ui.put(
-                    egui::Rect::from_min_size(
-                        egui::pos2(
-                            // index times width+padding + beginning padding
-                            (x as f32) * (pw + pw * pp) + pw * pp,
-                            (y as f32) * (pw + pw * pp) + pw * pp,
-                        ),
-                        egui::vec2(pw, pw),
-                    ),
-                    pixel,
-                );
-
-                // Handle pixel click
-                if px_response.clicked() {
-                    let old_highlight = self.main_app.highlight_idx;
-                    self.main_app.highlight_idx = (x, y);
-                    if !self.main_app.has_selected_hit {
-                        self.main_app.has_selected_hit = true;
-                    } else if old_highlight == self.main_app.highlight_idx {
-                        self.main_app.has_selected_hit = !self.main_app.has_selected_hit;
+        if let Some(selection) = self.main_app.selection {
+            let (x, y) = selection.pixel;
+            painter.rect_stroke(
+                grid.pixel_rect(x, y),
+                0.0,
+                egui::Stroke::new(3.0, egui::Color32::from_rgb(255, 255, 255)),
+            );
+        }
+
+        // Preview where the word in the builder lands
+        if self.main_app.show_builder {
+            let dw = &self.main_app.builder_word;
+            for (pixels, color) in [
+                (dw.pileup_pixels(), egui::Color32::from_rgb(255, 140, 0)),
+                (dw.hit_pixels(), egui::Color32::YELLOW),
+            ] {
+                for (x, y) in pixels {
+                    if x < self.w_pixels && y < self.h_pixels {
+                        painter.rect_stroke(
+                            grid.pixel_rect(x, y),
+                            0.0,
+                            egui::Stroke::new(2.0, color),
+                        );
                     }
                 }
             }
         }
+
+        // Handle pixel click, the pixel is found from the pointer position
+        if response.clicked() {
+            if let Some(pixel) = response
+                .interact_pointer_pos()
+                .and_then(|p| grid.pixel_at(p))
+            {
+                let word = self
+                    .main_app
+                    .hit_map
+                    .word_at(pixel.0, pixel.1)
+                    .map(|word_idx| (self.main_app.analysis_chunk_idx, word_idx));
+                self.main_app.toggle_selection(pixel, word);
+            }
+        }
+
         response
     }
 }
@@ -1,8 +1,322 @@
 use eframe::egui;
-use std::collections::{BTreeSet, HashMap};
 
+use crate::calibration::Calibration;
+use crate::colors::ColorMode;
+use crate::render::Surface;
+use crate::tdcpix::{frame_offset, Chunk, DataWord, FRAME_PERIOD_PS, HA_PR_COL, MAPPED_COLUMNS};
 use crate::tdcpixapp::TDCpixApp;
 
+// Distance in px within which a cursor can be grabbed or snaps to a box edge
+const CURSOR_GRAB_PX: f32 = 6.0;
+const CURSOR_SNAP_PX: f32 = 6.0;
+const CURSOR_COLORS: [egui::Color32; 2] = [
+    egui::Color32::from_rgb(0, 200, 255),
+    egui::Color32::from_rgb(255, 80, 200),
+];
+
+/// One data word as drawn on the timeline.
+pub struct TimelineBox {
+    pub chunk_idx: usize,
+    pub word_idx: usize,
+    // Times in ps from the start of the frame of the current chunk
+    pub start: i64,
+    pub duration: u64,
+    pub lane: usize,
+    // Overlapping boxes in a lane are stacked in sub rows
+    pub sub_row: usize,
+    pub pixel: (u8, u8),
+    pub has_pileup: bool,
+}
+
+/// What the words are grouped into lanes by.
+#[derive(Clone, Copy, PartialEq, Debug, clap::ValueEnum)]
+pub enum LaneGrouping {
+    Address,
+    Column,
+    Pixel,
+}
+
+impl LaneGrouping {
+    pub const ALL: [LaneGrouping; 3] = [
+        LaneGrouping::Address,
+        LaneGrouping::Column,
+        LaneGrouping::Pixel,
+    ];
+
+    fn key(&self, dw: &DataWord) -> u16 {
+        let (x, y) = dw.pixel();
+        match self {
+            LaneGrouping::Address => dw.address as u16,
+            LaneGrouping::Column => x as u16,
+            LaneGrouping::Pixel => (x as u16) << 8 | y as u16,
+        }
+    }
+
+    fn label(&self, key: u16) -> String {
+        match self {
+            // Mapped addresses also get the column and arbiter they belong to
+            LaneGrouping::Address if key < (MAPPED_COLUMNS * HA_PR_COL) as u16 => {
+                let address = key as u8;
+                format!(
+                    "addr {} (x{}, arb {})",
+                    address,
+                    address / HA_PR_COL,
+                    address % HA_PR_COL
+                )
+            }
+            LaneGrouping::Address => format!("addr {}", key),
+            LaneGrouping::Column => format!("x {}", key),
+            LaneGrouping::Pixel => format!("({}, {})", key >> 8, key & 0xFF),
+        }
+    }
+}
+
+/// Visible time range of the timeline in ps.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct TimeView {
+    pub start: f64,
+    pub end: f64,
+}
+
+impl TimeView {
+    // Zooming in further than this makes no sense with 98 ps fine time bins
+    const MIN_SPAN: f64 = 10.0;
+
+    pub fn span(&self) -> f64 {
+        self.end - self.start
+    }
+
+    pub fn x_of(&self, t: f64, rect: egui::Rect) -> f32 {
+        rect.left() + ((t - self.start) / self.span()) as f32 * rect.width()
+    }
+
+    pub fn time_at(&self, x: f32, rect: egui::Rect) -> f64 {
+        self.start + ((x - rect.left()) / rect.width()) as f64 * self.span()
+    }
+
+    /// Scales the view by `factor` keeping time `t` at the same place.
+    pub fn zoom(&mut self, factor: f64, t: f64) {
+        let factor = factor.max(Self::MIN_SPAN / self.span());
+        self.start = t - (t - self.start) * factor;
+        self.end = t + (self.end - t) * factor;
+    }
+
+    pub fn pan(&mut self, dt: f64) {
+        self.start += dt;
+        self.end += dt;
+    }
+}
+
+/// Start of a frame on the timeline.
+pub struct FrameMark {
+    pub frame_counter: u32,
+    pub start: i64,
+}
+
+/// Everything the timeline needs that only depends on the shown chunks. It is
+/// kept in the app and only rebuilt when the chunks change, the screen
+/// positions are only recomputed when the widget rect or view changes.
+pub struct TimelineLayout {
+    // (chunks_generation, current chunk, shown chunks, grouping) this layout was made for
+    key: (u64, usize, (usize, usize), LaneGrouping),
+    pub boxes: Vec<TimelineBox>,
+    pub frames: Vec<FrameMark>,
+    pub lane_labels: Vec<String>,
+    // First sub row of each lane, the last entry is the total number of rows
+    pub lane_rows: Vec<usize>,
+    pub t_min: i64,
+    pub t_max: i64,
+    // Screen rects of the boxes for `rects_for`
+    pub rects: Vec<egui::Rect>,
+    rects_for: (egui::Rect, TimeView),
+}
+
+/// The `first..last` chunks of a window of `window` chunks around `current`.
+pub fn shown_range(len: usize, current: usize, window: usize) -> (usize, usize) {
+    let window = window.max(1);
+    let last = (current.saturating_sub((window - 1) / 2) + window).min(len);
+    (last.saturating_sub(window), last)
+}
+
+impl TimelineLayout {
+    /// Lays out a window of `window` consecutive chunks around `current`. Times
+    /// are relative to the start of the frame of `current`.
+    pub fn new(
+        chunks_generation: u64,
+        chunks: &[Chunk],
+        current: usize,
+        window: usize,
+        grouping: LaneGrouping,
+    ) -> Self {
+        let (first, last) = shown_range(chunks.len(), current, window);
+        let shown = &chunks[first..last];
+        let reference = chunks[current].frame_word.frame_counter;
+
+        // Create a sorted list of unique lane keys for the data words
+        let mut lanes: Vec<u16> = shown
+            .iter()
+            .flat_map(|c| c.data_words.iter().map(|dw| grouping.key(dw)))
+            .collect();
+        lanes.sort_unstable();
+        lanes.dedup();
+
+        let mut boxes: Vec<TimelineBox> = Vec::new();
+        let mut frames: Vec<FrameMark> = Vec::new();
+        for (chunk_idx, chunk) in (first..last).zip(shown) {
+            let frame_counter = chunk.frame_word.frame_counter;
+            let frame_start = frame_offset(frame_counter, reference) * FRAME_PERIOD_PS as i64;
+            frames.push(FrameMark {
+                frame_counter,
+                start: frame_start,
+            });
+            boxes.extend(
+                chunk
+                    .data_words
+                    .iter()
+                    .enumerate()
+                    .map(|(word_idx, dw)| TimelineBox {
+                        chunk_idx,
+                        word_idx,
+                        start: frame_start + dw.get_start_time() as i64,
+                        duration: dw.get_duration(),
+                        lane: lanes.binary_search(&grouping.key(dw)).unwrap(),
+                        sub_row: 0,
+                        pixel: dw.pixel(),
+                        has_pileup: dw.address_pileup != 0,
+                    }),
+            );
+        }
+
+        // Stack overlapping boxes: each box goes into the first sub row of its
+        // lane that is free at its start time
+        let mut order: Vec<usize> = (0..boxes.len()).collect();
+        order.sort_by_key(|&i| (boxes[i].lane, boxes[i].start));
+        let mut lane_sub_rows = vec![0; lanes.len()];
+        let mut row_ends: Vec<i64> = Vec::new();
+        for (n, &i) in order.iter().enumerate() {
+            if n == 0 || boxes[order[n - 1]].lane != boxes[i].lane {
+                row_ends.clear();
+            }
+            let end = boxes[i].start + boxes[i].duration as i64;
+            let sub_row = match row_ends.iter().position(|&e| e <= boxes[i].start) {
+                Some(r) => r,
+                None => {
+                    row_ends.push(end);
+                    row_ends.len() - 1
+                }
+            };
+            row_ends[sub_row] = end;
+            boxes[i].sub_row = sub_row;
+            lane_sub_rows[boxes[i].lane] = lane_sub_rows[boxes[i].lane].max(sub_row + 1);
+        }
+        let mut lane_rows = vec![0];
+        for rows in lane_sub_rows {
+            lane_rows.push(lane_rows[lane_rows.len() - 1] + rows);
+        }
+
+        let mut t_min = boxes.iter().map(|b| b.start).min().unwrap_or(0);
+        let mut t_max = boxes
+            .iter()
+            .map(|b| b.start + b.duration as i64)
+            .max()
+            .unwrap_or(0);
+        // With several frames shown, all of them should fit
+        if frames.len() > 1 {
+            t_min = t_min.min(frames[0].start);
+            t_max = t_max.max(frames[frames.len() - 1].start + FRAME_PERIOD_PS as i64);
+        }
+
+        TimelineLayout {
+            key: (chunks_generation, current, (first, last), grouping),
+            boxes,
+            frames,
+            lane_labels: lanes.iter().map(|&k| grouping.label(k)).collect(),
+            lane_rows,
+            t_min,
+            t_max,
+            rects: Vec::new(),
+            rects_for: (
+                egui::Rect::NOTHING,
+                TimeView {
+                    start: 0.0,
+                    end: 0.0,
+                },
+            ),
+        }
+    }
+
+    /// Whether the layout shows the same chunks, appended chunks only matter
+    /// when they are in the window.
+    pub fn is_for(
+        &self,
+        chunks_generation: u64,
+        chunks_len: usize,
+        current: usize,
+        window: usize,
+        grouping: LaneGrouping,
+    ) -> bool {
+        let shown = shown_range(chunks_len, current, window);
+        self.key == (chunks_generation, current, shown, grouping)
+    }
+
+    pub fn row_height(&self, rect: egui::Rect) -> f32 {
+        rect.height() / self.lane_rows[self.lane_rows.len() - 1].max(1) as f32
+    }
+
+    /// The view showing all shown chunks.
+    pub fn full_view(&self) -> TimeView {
+        TimeView {
+            start: self.t_min as f64,
+            // Guard against a zero time range for a single word without duration
+            end: self.t_max.max(self.t_min + 1) as f64,
+        }
+    }
+
+    /// Updates the screen rects of the boxes for drawing `view` into `rect`.
+    pub fn update_rects(&mut self, rect: egui::Rect, view: TimeView) {
+        if self.rects_for != (rect, view) {
+            let row_height = self.row_height(rect);
+            self.rects = self
+                .boxes
+                .iter()
+                .map(|b| {
+                    let x0 = view.x_of(b.start as f64, rect);
+                    let x1 = view.x_of((b.start + b.duration as i64) as f64, rect);
+                    let row = self.lane_rows[b.lane] + b.sub_row;
+                    egui::Rect::from_min_max(
+                        egui::pos2(x0, rect.top() + row as f32 * row_height),
+                        egui::pos2(x1, rect.top() + (row + 1) as f32 * row_height),
+                    )
+                })
+                .collect();
+            self.rects_for = (rect, view);
+        }
+    }
+
+    /// Index of the topmost box under `pos`.
+    pub fn box_at(&self, pos: egui::Pos2) -> Option<usize> {
+        self.rects.iter().rposition(|r| r.contains(pos))
+    }
+
+    /// Time of the leading or trailing box edge closest to `x`, if one is
+    /// within snapping distance.
+    pub fn edge_near(&self, x: f32) -> Option<f64> {
+        self.boxes
+            .iter()
+            .zip(&self.rects)
+            .flat_map(|(b, r)| {
+                [
+                    (r.left(), b.start),
+                    (r.right(), b.start + b.duration as i64),
+                ]
+            })
+            .map(|(edge_x, t)| ((edge_x - x).abs(), t))
+            .filter(|&(d, _)| d <= CURSOR_SNAP_PX)
+            .min_by(|a, b| a.0.total_cmp(&b.0))
+            .map(|(_, t)| t as f64)
+    }
+}
+
 pub struct Timeline<'a> {
     main_app: &'a mut TDCpixApp,
 }
@@ -15,157 +329,540 @@ impl<'a> Timeline<'a> {
 
 impl<'a> egui::Widget for Timeline<'a> {
     fn ui(self, ui: &mut egui::Ui) -> egui::Response {
-        // Get ui y offset in current panel
-        let ui_y_offset = ui.min_rect().top();
+        let app = self.main_app;
+
+        let mut toggle_cursors = false;
+        ui.horizontal_wrapped(|ui| {
+            if ui.button("Reset zoom").clicked() {
+                app.timeline_view = None;
+            }
+            toggle_cursors = ui
+                .selectable_label(app.timeline_cursors.is_some(), "Cursors")
+                .on_hover_text(
+                    "Drag the cursors to measure, they snap to box edges unless Alt is held",
+                )
+                .clicked();
+            ui.label("Chunks:");
+            ui.add(egui::DragValue::new(&mut app.timeline_chunks).clamp_range(1..=64))
+                .on_hover_text("Number of consecutive chunks shown around the current one");
+            egui::ComboBox::from_label("Lanes")
+                .selected_text(format!("{:?}", app.timeline_grouping))
+                .show_ui(ui, |ui| {
+                    for grouping in LaneGrouping::ALL {
+                        ui.selectable_value(
+                            &mut app.timeline_grouping,
+                            grouping,
+                            format!("{:?}", grouping),
+                        );
+                    }
+                });
+            egui::ComboBox::from_label("Color")
+                .selected_text(app.color_mode.name())
+                .show_ui(ui, |ui| {
+                    for mode in ColorMode::ALL {
+                        ui.selectable_value(&mut app.color_mode, mode, mode.name());
+                    }
+                });
 
-        // Get available height
-        let height_avail = self.main_app.w_dim.y - ui_y_offset;
+            // Legend
+            for (label, color) in app.color_mode.legend() {
+                let (swatch, _) =
+                    ui.allocate_exact_size(egui::vec2(10.0, 10.0), egui::Sense::hover());
+                ui.painter().rect_filled(swatch, 0.0, color);
+                ui.label(egui::RichText::new(label).small());
+            }
+            if app.color_mode == ColorMode::Calibration {
+                if ui
+                    .small_button("Load...")
+                    .on_hover_text(
+                        "Csv of x,y,time_offset_ps lines, an offset of 'masked' masks the pixel",
+                    )
+                    .clicked()
+                {
+                    let mut dialog = egui_file::FileDialog::open_file(app.file_path.clone());
+                    dialog.open();
+                    app.calibration_dialog = Some(dialog);
+                }
+                match &app.calibration_error {
+                    Some(e) => ui
+                        .colored_label(ui.visuals().error_fg_color, egui::RichText::new(e).small()),
+                    None => ui.label(
+                        egui::RichText::new(format!("{} pixels calibrated", app.calibration.len()))
+                            .small(),
+                    ),
+                };
+            }
+            if let Some(dialog) = &mut app.calibration_dialog {
+                if dialog.show(ui.ctx()).selected() {
+                    if let Some(file) = dialog.path() {
+                        // A file that fails to load keeps the calibration there was
+                        match Calibration::load(&file) {
+                            Ok(calibration) => {
+                                app.calibration = calibration;
+                                app.calibration_error = None;
+                            }
+                            Err(e) => app.calibration_error = Some(e),
+                        }
+                    }
+                }
+            }
 
-        // Allocate space for widget (response for this widget is unused)
-        let (_rect, response) = ui.allocate_exact_size(
-            egui::vec2(self.main_app.w_dim.x, height_avail),
-            egui::Sense::click(),
-        );
+            if let Some((start, end)) = app.time_filter {
+                ui.separator();
+                ui.label(format!(
+                    "Window: {} – {}",
+                    format_time_precise(start),
+                    format_time_precise(end)
+                ));
+                if ui.small_button("Clear").clicked() {
+                    app.time_filter = None;
+                }
+            }
+
+            if let Some([c1, c2]) = app.timeline_cursors {
+                ui.separator();
+                ui.label(format!(
+                    "C1: {}  C2: {}  Δ: {}",
+                    format_time_precise(c1),
+                    format_time_precise(c2),
+                    format_time_precise(c2 - c1)
+                ));
+            }
+        });
 
-        if self.main_app.analysis_chunk_idx >= self.main_app.chunks.len() {
+        // Allocate the rest of the space for the boxes and the time axis below them
+        let (rect, response) =
+            ui.allocate_exact_size(ui.available_size(), egui::Sense::click_and_drag());
+        let plot_rect = plot_rect(rect);
+
+        if app.analysis_chunk_idx >= app.chunks.len() {
             return response;
         }
 
-        let chunk = &self.main_app.chunks[self.main_app.analysis_chunk_idx];
+        let is_cached = app.timeline_layout.as_ref().is_some_and(|l| {
+            l.is_for(
+                app.chunks_generation,
+                app.chunks.len(),
+                app.analysis_chunk_idx,
+                app.timeline_chunks,
+                app.timeline_grouping,
+            )
+        });
+        if !is_cached {
+            app.timeline_layout = Some(TimelineLayout::new(
+                app.chunks_generation,
+                &app.chunks,
+                app.analysis_chunk_idx,
+                app.timeline_chunks,
+                app.timeline_grouping,
+            ));
+        }
 
-        let dw_num = chunk.data_words.len();
+        // Zoom with the mouse wheel around the pointer and pan by dragging
+        let mut view = app
+            .timeline_view
+            .unwrap_or_else(|| app.timeline_layout.as_ref().unwrap().full_view());
+        if let Some(pos) = response.hover_pos() {
+            let scroll = ui.input(|i| i.scroll_delta.y);
+            if scroll != 0.0 {
+                view.zoom(
+                    (-scroll as f64 * 0.002).exp(),
+                    view.time_at(pos.x, plot_rect),
+                );
+                app.timeline_view = Some(view);
+            }
+        }
+        if toggle_cursors {
+            app.timeline_cursors = match app.timeline_cursors {
+                Some(_) => None,
+                None => Some([
+                    view.start + view.span() / 3.0,
+                    view.start + view.span() * 2.0 / 3.0,
+                ]),
+            };
+        }
 
-        // Create vector of times from each data word
-        // let dw_times: Vec<u64> = chunk.data_words.iter().map(|dw| dw.get_time()).collect();
-        let dw_times: Vec<(u64, u64)> = chunk
-            .data_words
-            .iter()
-            .map(|dw| (dw.get_start_time(), dw.get_duration()))
-            .collect();
+        // Dragging with the secondary button, or with shift held, brushes a
+        // time window that the pixel grid is restricted to
+        let brush_id = response.id.with("brush_anchor");
+        let brushing = response.dragged_by(egui::PointerButton::Secondary)
+            || (response.dragged() && ui.input(|i| i.modifiers.shift));
+        if response.drag_started() && brushing {
+            let anchor = response
+                .interact_pointer_pos()
+                .map(|pos| view.time_at(pos.x, plot_rect));
+            ui.data_mut(|d| d.insert_temp(brush_id, anchor));
+        }
+        let brush_anchor: Option<f64> = ui.data(|d| d.get_temp(brush_id)).flatten();
+        if let (Some(anchor), Some(pos)) = (brush_anchor, response.interact_pointer_pos()) {
+            if response.dragged() {
+                let t = view.time_at(pos.x, plot_rect);
+                app.time_filter = Some((anchor.min(t), anchor.max(t)));
+            }
+        }
 
-        // let box_widths = vec![self.main_app.w_dim.x / (dw_times.len() as f32); dw_times.len()];
-        // Calculate the width of each box based on the duration of the data word
-        let box_widths: Vec<f32> = {
-            let max_time = dw_times.iter().map(|(t, d)| t + d).max().unwrap_or(0);
-            let min_time = dw_times.iter().map(|(t, _)| t).min().unwrap_or(&0);
-            let time_range = max_time - min_time;
-            let width_percentage = dw_times
-                .iter()
-                .map(|(_, d)| (*d) as f32 / time_range as f32);
-            width_percentage
-                .map(|p| p * self.main_app.w_dim.x)
-                .collect::<Vec<f32>>()
+        // A drag that starts on a cursor moves the cursor instead of panning
+        let grab_id = response.id.with("grabbed_cursor");
+        let near_cursor = |pos: egui::Pos2| {
+            app.timeline_cursors.and_then(|cursors| {
+                (0..2)
+                    .map(|i| (i, (view.x_of(cursors[i], plot_rect) - pos.x).abs()))
+                    .filter(|&(_, d)| d <= CURSOR_GRAB_PX)
+                    .min_by(|a, b| a.1.total_cmp(&b.1))
+                    .map(|(i, _)| i)
+            })
         };
+        if response.drag_started() && !brushing {
+            let grabbed = response.interact_pointer_pos().and_then(near_cursor);
+            ui.data_mut(|d| d.insert_temp(grab_id, grabbed));
+        }
+        let grabbed: Option<usize> = ui.data(|d| d.get_temp(grab_id)).flatten();
+        if response.hover_pos().and_then(near_cursor).is_some() || grabbed.is_some() {
+            ui.ctx().set_cursor_icon(egui::CursorIcon::ResizeHorizontal);
+        }
+        if response.dragged_by(egui::PointerButton::Primary)
+            && grabbed.is_none()
+            && brush_anchor.is_none()
+        {
+            let dx = response.drag_delta().x;
+            view.pan(-(dx / plot_rect.width()) as f64 * view.span());
+            app.timeline_view = Some(view);
+        }
+        if response.drag_released() {
+            // A click without moving clears the window instead of leaving an empty one
+            if let Some((start, end)) = app.time_filter.filter(|_| brush_anchor.is_some()) {
+                if view.x_of(end, plot_rect) - view.x_of(start, plot_rect) < 2.0 {
+                    app.time_filter = None;
+                }
+            }
+            ui.data_mut(|d| {
+                d.remove::<Option<usize>>(grab_id);
+                d.remove::<Option<f64>>(brush_id);
+            });
+        }
 
-        // Create a set of unique addresses for the data words
-        let groups = BTreeSet::from_iter(chunk.data_words.iter().map(|dw| dw.address));
+        let layout = app.timeline_layout.as_mut().unwrap();
+        layout.update_rects(plot_rect, view);
+        let layout = app.timeline_layout.as_ref().unwrap();
 
-        // Create a lookup table to determine if a data word has pileup
-        let dw_has_pileup_lut = HashMap::<u8, bool>::from_iter(
-            chunk
-                .data_words
-                .iter()
-                .enumerate()
-                .map(|(i, dw)| (i as u8, dw.address_pileup != 0)),
+        if let (Some(i), Some(pos), Some(cursors)) = (
+            grabbed.filter(|_| response.drag_delta() != egui::Vec2::ZERO),
+            response.interact_pointer_pos(),
+            app.timeline_cursors.as_mut(),
+        ) {
+            let snap = !ui.input(|i| i.modifiers.alt);
+            cursors[i] = match layout.edge_near(pos.x).filter(|_| snap) {
+                Some(t) => t,
+                None => view.time_at(pos.x, plot_rect),
+            };
+        }
+
+        let text_color = ui.visuals().text_color();
+        let mut outer_painter = ui.painter().clone();
+        draw_time_axis(
+            &mut outer_painter,
+            &view,
+            plot_rect,
+            rect.bottom(),
+            text_color,
+        );
+        draw_lane_labels(
+            &mut outer_painter,
+            layout,
+            plot_rect,
+            rect.left(),
+            text_color,
+        );
+        let mut painter = ui.painter_at(plot_rect);
+
+        // Brushed time window
+        if let Some((start, end)) = app.time_filter {
+            let window = egui::Rect::from_x_y_ranges(
+                view.x_of(start, plot_rect)..=view.x_of(end, plot_rect),
+                plot_rect.y_range(),
+            );
+            painter.rect_filled(
+                window,
+                0.0,
+                egui::Color32::from_rgba_unmultiplied(255, 255, 255, 20),
+            );
+        }
+
+        draw_boxes(
+            &mut painter,
+            layout,
+            &view,
+            plot_rect,
+            &app.chunks,
+            app.analysis_chunk_idx,
+            app.color_mode,
+            &app.calibration,
         );
 
-        // Calculate the height of each box based on the number of unique addresses
-        let box_height = height_avail / groups.len() as f32;
+        for (tl_box, box_rect) in layout.boxes.iter().zip(&layout.rects) {
+            // Highlight the box if it is the selected hit or pileup, a
+            // selection without a word highlights every hit of the pixel
+            let (x, y) = tl_box.pixel;
+            let selected = app.selection.map(|s| s.pixel);
+            let is_highlighted_hit = match app.selection.and_then(|s| s.word) {
+                Some(word) => word == (tl_box.chunk_idx, tl_box.word_idx),
+                None => selected == Some((x, y)),
+            };
 
-        // Calculate the y offset for each box based on its group
-        let box_yoffsets = {
-            let mut offsets = vec![0.0; dw_times.len()];
-            for (i, dw) in chunk.data_words.iter().enumerate() {
-                offsets[i] = (groups.range(..dw.address).count() as f32) * box_height;
+            // Pileup concerns all pixels served by the same arbiter
+            let is_highlighted_pileup = tl_box.has_pileup
+                && selected.is_some_and(|(sx, sy)| sx == x && sy % HA_PR_COL == y % HA_PR_COL);
+
+            if is_highlighted_hit || is_highlighted_pileup {
+                painter.rect_stroke(*box_rect, 0.0, egui::Stroke::new(3.0, egui::Color32::WHITE));
             }
-            offsets
-        };
+        }
 
-        // Calculate the x offset for each box based on its start time
-        let box_xoffsets = {
-            let max_time = dw_times.iter().map(|(t, d)| t + d).max().unwrap_or(1);
-            let min_time = dw_times.iter().map(|(t, _)| t).min().unwrap_or(&0);
-            let rel_start_times = dw_times
-                .iter()
-                .map(|(t, _)| (t - min_time) as f32 / (max_time - min_time) as f32);
+        // Measurement cursors
+        if let Some(cursors) = app.timeline_cursors {
+            for (i, t) in cursors.iter().enumerate() {
+                let x = view.x_of(*t, plot_rect);
+                let color = CURSOR_COLORS[i];
+                painter.line_segment(
+                    [
+                        egui::pos2(x, plot_rect.top()),
+                        egui::pos2(x, plot_rect.bottom()),
+                    ],
+                    egui::Stroke::new(1.5, color),
+                );
+                painter.text(
+                    egui::pos2(x + 2.0, plot_rect.bottom()),
+                    egui::Align2::LEFT_BOTTOM,
+                    format!("C{}", i + 1),
+                    egui::FontId::proportional(11.0),
+                    color,
+                );
+            }
+        }
 
-            rel_start_times
-                .map(|t| t * self.main_app.w_dim.x)
-                .collect::<Vec<f32>>()
+        // Show a tooltip when hovering over a box
+        let hovered_box = response
+            .hover_pos()
+            .filter(|pos| plot_rect.contains(*pos))
+            .and_then(|pos| layout.box_at(pos));
+        let clicked_box = hovered_box.filter(|_| response.clicked()).map(|i| {
+            (
+                layout.boxes[i].pixel,
+                layout.boxes[i].chunk_idx,
+                layout.boxes[i].word_idx,
+            )
+        });
+        let response = match hovered_box {
+            Some(i) => {
+                let tl_box = &layout.boxes[i];
+                let hover_text = format!(
+                    "Chunk: {}\nPixel coord: {}, {}\nStart: {}\nDuration: {}",
+                    tl_box.chunk_idx,
+                    tl_box.pixel.0,
+                    tl_box.pixel.1,
+                    format_time_precise(tl_box.start as f64),
+                    format_time_precise(tl_box.duration as f64)
+                );
+                response.on_hover_text_at_pointer(egui::RichText::new(hover_text))
+            }
+            None => response,
         };
 
-        // Draw each box
-        for (i, box_width) in box_widths.iter().enumerate() {
-            let box_x = box_xoffsets[i];
-            let box_y = ui_y_offset + box_yoffsets[i];
-            let box_color = egui::Color32::from_rgb(
-                (255.0 * ((i as f32) / (dw_num as f32))) as u8,
-                0,
-                (255.0 * (1.0 - (i as f32) / (dw_num as f32))) as u8,
-            );
-
-            let placement = egui::pos2(box_x, box_y);
-            let rect = egui::Rect::from_min_size(placement, egui::vec2(*box_width, box_height));
+        // Select the box when clicked
+        if let Some((pixel, chunk_idx, word_idx)) = clicked_box {
+            app.toggle_selection(pixel, Some((chunk_idx, word_idx)));
+        }
 
-            ui.painter().rect_filled(rect, 0.0, box_color);
+        response
+    }
+}
 
-            // Highlight the box if it contains the selected hit or pileup
-            if let Some(idx) = self.main_app.hit_idxes.get(i) {
-                let is_highlighted_hit =
-                    self.main_app.has_selected_hit && self.main_app.highlight_idx == *idx;
+// Room left of the boxes for the lane labels and below them for the time axis
+const LABEL_WIDTH: f32 = 110.0;
+const AXIS_HEIGHT: f32 = 20.0;
 
-                let is_highlighted_pileup =
-                    dw_has_pileup_lut[&(i as u8)] && self.main_app.has_selected_hit && {
-                        let mut pileup_idxes: [(u8, u8); 5] = [(0, 0); 5];
-                        for (arbit, item) in pileup_idxes.iter_mut().enumerate() {
-                            *item = (idx.0, idx.1 % 9 + ((arbit as u8) * 9));
-                        }
-                        pileup_idxes.contains(&self.main_app.highlight_idx)
-                    };
-
-                if is_highlighted_hit || is_highlighted_pileup {
-                    ui.painter().rect_stroke(
-                        rect,
-                        0.0,
-                        egui::Stroke::new(3.0, egui::Color32::WHITE),
-                    );
-                }
+/// Part of `rect` the boxes go in, leaving room for the lane labels and time axis.
+pub fn plot_rect(rect: egui::Rect) -> egui::Rect {
+    egui::Rect::from_min_max(
+        rect.min + egui::vec2(LABEL_WIDTH, 0.0),
+        egui::pos2(rect.right(), rect.bottom() - AXIS_HEIGHT),
+    )
+}
 
-                // Show a tooltip when hovering over the box
-                let hover_response = ui.interact(
-                    rect,
-                    egui::Id::new(format!("tline_box_hover{}", i)),
-                    egui::Sense::hover(),
-                );
-                if hover_response.hovered() {
-                    let hover_text = format!(
-                        "Pixel coord: {}, {}\nTime: {} ns\nDEBUG: {}",
-                        idx.0,
-                        idx.1,
-                        chunk.data_words[i].get_duration(),
-                        *box_width
-                    );
-                    hover_response.on_hover_text(egui::RichText::new(hover_text));
-                }
+/// Draws a complete timeline of `layout` into `rect`, as used for exported images.
+#[allow(clippy::too_many_arguments)]
+pub fn draw_timeline(
+    s: &mut impl Surface,
+    rect: egui::Rect,
+    layout: &mut TimelineLayout,
+    view: TimeView,
+    chunks: &[Chunk],
+    current: usize,
+    color_mode: ColorMode,
+    calibration: &Calibration,
+    text_color: egui::Color32,
+) {
+    let plot_rect = plot_rect(rect);
+    layout.update_rects(plot_rect, view);
+    draw_time_axis(s, &view, plot_rect, rect.bottom(), text_color);
+    draw_lane_labels(s, layout, plot_rect, rect.left(), text_color);
+    draw_boxes(
+        s,
+        layout,
+        &view,
+        plot_rect,
+        chunks,
+        current,
+        color_mode,
+        calibration,
+    );
+}
 
-                // Select the box when clicked
-                let click_response = ui.interact(
-                    rect,
-                    egui::Id::new(format!("tline_box{}", i)),
-                    egui::Sense::click(),
-                );
-                if click_response.clicked() {
-                    let old_highlight = self.main_app.highlight_idx;
-                    self.main_app.highlight_idx = self.main_app.hit_idxes[i];
-                    if !self.main_app.has_selected_hit {
-                        self.main_app.has_selected_hit = true;
-                    } else if old_highlight == self.main_app.highlight_idx {
-                        self.main_app.has_selected_hit = !self.main_app.has_selected_hit;
-                    }
-                }
+/// Draws the frame boundaries and the boxes of `layout`, which must have its
+/// rects updated for `plot_rect`. Words of chunks other than `current` are dimmed.
+#[allow(clippy::too_many_arguments)]
+pub fn draw_boxes(
+    s: &mut impl Surface,
+    layout: &TimelineLayout,
+    view: &TimeView,
+    plot_rect: egui::Rect,
+    chunks: &[Chunk],
+    current: usize,
+    color_mode: ColorMode,
+    calibration: &Calibration,
+) {
+    let frame_color = egui::Color32::from_rgb(200, 200, 0);
+    if layout.frames.len() > 1 {
+        for frame in &layout.frames {
+            let x = view.x_of(frame.start as f64, plot_rect);
+            if !plot_rect.x_range().contains(&x) {
+                continue;
             }
+            s.line(
+                [
+                    egui::pos2(x, plot_rect.top()),
+                    egui::pos2(x, plot_rect.bottom()),
+                ],
+                1.0,
+                frame_color,
+            );
+            s.text(
+                egui::pos2(x + 2.0, plot_rect.top()),
+                egui::Align2::LEFT_TOP,
+                &format!("frame {}", frame.frame_counter),
+                11.0,
+                frame_color,
+            );
         }
+    }
 
-        response
+    for (tl_box, box_rect) in layout.boxes.iter().zip(&layout.rects) {
+        let box_rect = box_rect.intersect(plot_rect);
+        if !box_rect.is_positive() {
+            continue;
+        }
+        let dw = &chunks[tl_box.chunk_idx].data_words[tl_box.word_idx];
+        let mut box_color = color_mode.color(dw, calibration);
+        // Words of the neighbouring chunks are dimmed
+        if tl_box.chunk_idx != current {
+            box_color = box_color.gamma_multiply(0.5);
+        }
+        s.fill_rect(box_rect, box_color);
+    }
+}
+
+/// Draws the lane labels left of `plot_rect` starting at `left`, and a line
+/// between the lanes.
+fn draw_lane_labels(
+    s: &mut impl Surface,
+    layout: &TimelineLayout,
+    plot_rect: egui::Rect,
+    left: f32,
+    text_color: egui::Color32,
+) {
+    let row_height = layout.row_height(plot_rect);
+    for (lane, label) in layout.lane_labels.iter().enumerate() {
+        let top = plot_rect.top() + layout.lane_rows[lane] as f32 * row_height;
+        let bottom = plot_rect.top() + layout.lane_rows[lane + 1] as f32 * row_height;
+        if lane != 0 {
+            s.line(
+                [egui::pos2(left, top), egui::pos2(plot_rect.right(), top)],
+                1.0,
+                egui::Color32::from_rgb(60, 60, 60),
+            );
+        }
+        // Labels are skipped when the lanes get too thin to read them
+        if bottom - top >= 10.0 {
+            s.text(
+                egui::pos2(left + 2.0, (top + bottom) / 2.0),
+                egui::Align2::LEFT_CENTER,
+                label,
+                (bottom - top - 2.0).min(12.0),
+                text_color,
+            );
+        }
+    }
+}
+
+/// Draws tick marks and labels for `view` below `plot_rect`, down to `bottom`.
+fn draw_time_axis(
+    s: &mut impl Surface,
+    view: &TimeView,
+    plot_rect: egui::Rect,
+    bottom: f32,
+    text_color: egui::Color32,
+) {
+    let color = egui::Color32::from_rgb(100, 100, 100);
+    s.line(
+        [plot_rect.left_bottom(), plot_rect.right_bottom()],
+        1.0,
+        color,
+    );
+
+    // Roughly one labelled tick every 100 px
+    let step = tick_step(view.span() / (plot_rect.width() / 100.0).max(1.0) as f64);
+    let mut t = (view.start / step).ceil() * step;
+    while t <= view.end {
+        let x = view.x_of(t, plot_rect);
+        s.line(
+            [
+                egui::pos2(x, plot_rect.bottom()),
+                egui::pos2(x, plot_rect.bottom() + 4.0),
+            ],
+            1.0,
+            color,
+        );
+        s.text(
+            egui::pos2(x, bottom),
+            egui::Align2::CENTER_BOTTOM,
+            &format_time(t, step),
+            11.0,
+            text_color,
+        );
+        t += step;
+    }
+}
+
+/// Formats a time in ps as ns with ps precision.
+pub fn format_time_precise(t: f64) -> String {
+    format!("{:.3} ns", t / 1_000.0)
+}
+
+/// Rounds `raw` up to a step of 1, 2 or 5 times a power of ten.
+pub fn tick_step(raw: f64) -> f64 {
+    let magnitude = 10f64.powf(raw.max(1e-3).log10().floor());
+    [1.0, 2.0, 5.0, 10.0]
+        .iter()
+        .map(|m| m * magnitude)
+        .find(|&s| s >= raw)
+        .unwrap_or(10.0 * magnitude)
+}
+
+/// Formats a time in ps as ns, or as ps when `resolution` is below a ns.
+pub fn format_time(t: f64, resolution: f64) -> String {
+    if resolution >= 1_000.0 {
+        format!("{:.0} ns", t / 1_000.0)
+    } else {
+        format!("{:.0} ps", t)
     }
 }
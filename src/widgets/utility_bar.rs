@@ -15,101 +15,219 @@ impl<'a> UtilityBar<'a> {
     }
 
     fn update_file(&mut self, file_path: PathBuf) {
-        self.main_app.file_path = Some(file_path);
-        self.main_app.chunks.clear();
-        parse_tdcpix_txt(
-            self.main_app.file_path.as_ref().unwrap().to_str().unwrap(),
-            &mut self.main_app.chunks,
-        );
-        self.update_analysis_chunk_idx(0);
+        let was_following = self.main_app.follower.is_some();
+        let mut chunks = Vec::new();
+        if let Err(e) = parse_tdcpix_txt(&file_path, &mut chunks) {
+            // Keep showing the file that is loaded
+            self.main_app.file_error = Some(e);
+            return;
+        }
+        self.main_app.file_error = None;
+        self.main_app.set_chunks(chunks, Some(file_path));
+        if was_following {
+            self.main_app.start_following();
+        }
     }
 
     pub fn update_analysis_chunk_idx(&mut self, idx: usize) {
-        // Check if index is in bounds
-        if idx >= self.main_app.chunks.len() {
-            return;
+        self.main_app.update_analysis_chunk_idx(idx);
+    }
+
+    fn file_row(&mut self, ui: &mut egui::Ui) {
+        ui.label("Chunk idx:");
+        if ui
+            .text_edit_singleline(&mut self.main_app.idx_field_value)
+            .changed()
+        {
+            // If conversion is fine, update the chunk idx
+            // else ignore
+            if let Ok(idx) = self.main_app.idx_field_value.parse::<usize>() {
+                self.update_analysis_chunk_idx(idx);
+            }
         }
-        self.main_app.analysis_chunk_idx = idx;
-        self.main_app.hit_idxes.clear();
-        // self.main_app.arbiter_idxes.clear();
-        self.main_app.pileup_idxes.clear();
 
-        for dw in self.main_app.chunks[self.main_app.analysis_chunk_idx]
-            .data_words
-            .iter()
+        if ui.button("Q0").clicked() {
+            let mut dialog = FileDialog::open_file(self.main_app.file_path.clone());
+            dialog.open();
+            self.main_app.open_file_dialog = Some(dialog);
+        }
+
+        if let Some(dialog) = &mut self.main_app.open_file_dialog {
+            if dialog.show(ui.ctx()).selected() {
+                if let Some(file) = dialog.path() {
+                    self.update_file(file);
+                }
+            }
+        }
+
+        let mut follow = self.main_app.follower.is_some();
+        if ui
+            .add_enabled(
+                self.main_app.file_path.is_some(),
+                egui::Checkbox::new(&mut follow, "Follow"),
+            )
+            .on_hover_text("Watch the file for newly written frames")
+            .changed()
         {
-            let group_nr = dw.address;
-            // 5 groups in each column
-            // Arbiter shows which of the 5 pixels in group where hit
-            // "00001" means the first pixel was triggered
-            // "10000" means the last pixel was triggered
-            let arbiter_val = if dw.address_arbiter == 0 {
-                0
+            if follow {
+                self.main_app.start_following();
             } else {
-                dw.address_arbiter.trailing_zeros() as u8
-            };
-
-            // 9 groups and 9 arbiters in each column
-            const HA_PR_COL: u8 = 9;
-            const GRPS_PR_COL: u8 = HA_PR_COL;
-
-            let x = group_nr / GRPS_PR_COL; // Integer division
-
-            // Each pixel in a group are spaced 9 pixels apart (vertically)
-            let arbiter_nr = group_nr % HA_PR_COL; // Which arbiter is hit
-            let y = arbiter_nr + arbiter_val * HA_PR_COL; // Which pixel in the arbiter is hit
-
-            // add x,y to hits
-            self.main_app.hit_idxes.push((x, y));
-
-            // Handle pileup
-            let pileup = dw.address_pileup;
-            for bit in 0..5 {
-                if pileup & (1 << bit) != 0 {
-                    for pileup_pixels in 0..5 {
-                        self.main_app
-                            .pileup_idxes
-                            .push((x, arbiter_nr + pileup_pixels * HA_PR_COL))
-                    }
-                }
+                self.main_app.stop_following();
+            }
+        }
+        if follow || self.main_app.net_source.is_some() {
+            ui.checkbox(&mut self.main_app.auto_advance, "Auto-advance");
+        }
+        if let Some(follower) = &self.main_app.follower {
+            if let Some(e) = &follower.error {
+                ui.colored_label(ui.visuals().error_fg_color, e);
+            } else if follower.bad_lines > 0 {
+                ui.label(format!(
+                    "{} lines skipped, they could not be parsed",
+                    follower.bad_lines
+                ));
             }
         }
+
+        if ui.button("Chunks").clicked() {
+            self.main_app.show_chunk_browser = !self.main_app.show_chunk_browser;
+        }
+
+        if ui.button("Words").clicked() {
+            self.main_app.show_word_table = !self.main_app.show_word_table;
+        }
+
+        if ui.button("Inspect").clicked() {
+            self.main_app.show_inspector = !self.main_app.show_inspector;
+        }
+
+        if ui.button("Build").clicked() {
+            self.main_app.show_builder = !self.main_app.show_builder;
+        }
+
+        if ui.button("Export").clicked() {
+            self.main_app.show_export = !self.main_app.show_export;
+        }
+
+        if ui.button("Network").clicked() {
+            self.main_app.show_network = !self.main_app.show_network;
+        }
+
+        if ui.button("Generate").clicked() {
+            self.main_app.show_generator = !self.main_app.show_generator;
+        }
+
+        // ui.with_layout(egui::Layout::right_to_left(egui::Align::TOP), |ui| {
+        ui.label(format!("chunks: {}", self.main_app.chunks.len()));
+        // });
+
+        if let Some(e) = &self.main_app.file_error {
+            ui.colored_label(ui.visuals().error_fg_color, e);
+        }
     }
-}
 
-impl<'a> egui::Widget for UtilityBar<'a> {
-    fn ui(mut self, ui: &mut egui::Ui) -> egui::Response {
-        let resp = ui.horizontal(|ui| {
-            ui.label("Chunk idx:");
+    fn navigation_row(&mut self, ui: &mut egui::Ui) {
+        let app = &mut *self.main_app;
+        let has_chunks = !app.chunks.is_empty();
+
+        if ui
+            .add_enabled(has_chunks, egui::Button::new("◀"))
+            .on_hover_text("Previous chunk (left arrow)")
+            .clicked()
+        {
+            app.step_analysis_chunk_idx(-1);
+        }
+        if ui
+            .add_enabled(has_chunks, egui::Button::new("▶"))
+            .on_hover_text("Next chunk (right arrow)")
+            .clicked()
+        {
+            app.step_analysis_chunk_idx(1);
+        }
+        if has_chunks {
+            let mut idx = app.analysis_chunk_idx;
+            ui.spacing_mut().slider_width = 200.0;
             if ui
-                .text_edit_singleline(&mut self.main_app.idx_field_value)
+                .add(egui::Slider::new(&mut idx, 0..=app.chunks.len() - 1).show_value(false))
                 .changed()
             {
-                // If conversion is fine, update the chunk idx
-                // else ignore
-                if let Ok(idx) = self.main_app.idx_field_value.parse::<usize>() {
-                    self.update_analysis_chunk_idx(idx);
-                }
+                app.update_analysis_chunk_idx(idx);
             }
+        }
 
-            if ui.button("Q0").clicked() {
-                let mut dialog = FileDialog::open_file(self.main_app.file_path.clone());
-                dialog.open();
-                self.main_app.open_file_dialog = Some(dialog);
-            }
+        ui.separator();
+        let play_label = if app.playing { "⏸" } else { "⏵" };
+        if ui
+            .add_enabled(has_chunks, egui::Button::new(play_label))
+            .on_hover_text("Play through the chunks")
+            .clicked()
+        {
+            app.playing = !app.playing;
+        }
+        if ui
+            .add_enabled(has_chunks, egui::Button::new("Step"))
+            .on_hover_text("Next chunk, keeping the afterglow")
+            .clicked()
+        {
+            app.playing = false;
+            app.advance_playback();
+        }
+        ui.add(
+            egui::DragValue::new(&mut app.playback_fps)
+                .speed(0.1)
+                .clamp_range(0.1..=60.0)
+                .suffix(" fps"),
+        );
+        ui.checkbox(&mut app.show_afterglow, "Afterglow");
+        if app.show_afterglow {
+            ui.add(
+                egui::DragValue::new(&mut app.afterglow_half_life)
+                    .speed(0.1)
+                    .clamp_range(0.1..=100.0)
+                    .prefix("half-life "),
+            )
+            .on_hover_text("Chunks until the afterglow has faded to half");
+        }
 
-            if let Some(dialog) = &mut self.main_app.open_file_dialog {
-                if dialog.show(ui.ctx()).selected() {
-                    if let Some(file) = dialog.path() {
-                        self.main_app.file_path = Some(file);
-                        self.update_file(self.main_app.file_path.clone().unwrap());
-                    }
-                }
-            }
+        ui.separator();
+        ui.label("Next:");
+        let selected_pixel = app.selection.map(|s| s.pixel);
+        let mut target = None;
+        if ui
+            .add_enabled(selected_pixel.is_some(), egui::Button::new("pixel"))
+            .on_hover_text("Next chunk where the selected pixel fired")
+            .clicked()
+        {
+            target = selected_pixel.map(|(x, y)| JumpTarget::Pixel(x, y));
+        }
+        if ui
+            .add_enabled(has_chunks, egui::Button::new("pileup"))
+            .on_hover_text("Next chunk with a pileup flag")
+            .clicked()
+        {
+            target = Some(JumpTarget::Pileup);
+        }
+        if ui
+            .add_enabled(has_chunks, egui::Button::new("issue"))
+            .on_hover_text("Next chunk that failed validation")
+            .clicked()
+        {
+            target = Some(JumpTarget::Issue);
+        }
+        if let Some(target) = target {
+            app.jump_failed = !app.jump_to_next(target);
+        }
+        if app.jump_failed {
+            ui.label("no match");
+        }
+    }
+}
 
-            // ui.with_layout(egui::Layout::right_to_left(egui::Align::TOP), |ui| {
-            ui.label(format!("chunks: {}", self.main_app.chunks.len()));
-            // });
+impl<'a> egui::Widget for UtilityBar<'a> {
+    fn ui(mut self, ui: &mut egui::Ui) -> egui::Response {
+        let resp = ui.vertical(|ui| {
+            ui.horizontal(|ui| self.file_row(ui));
+            ui.horizontal(|ui| self.navigation_row(ui));
         });
         resp.response
     }
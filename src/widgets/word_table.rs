@@ -0,0 +1,181 @@
+use eframe::egui;
+use egui_extras::{Column, TableBuilder};
+
+use crate::tdcpix::DataWord;
+use crate::tdcpixapp::TDCpixApp;
+use crate::widgets::timeline::format_time_precise;
+
+/// Columns of the word table, each can be sorted by.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum WordColumn {
+    Index,
+    Raw,
+    Address,
+    Arbiter,
+    Pileup,
+    Leading,
+    Trailing,
+    Tot,
+    Pixel,
+}
+
+impl WordColumn {
+    pub const ALL: [WordColumn; 9] = [
+        WordColumn::Index,
+        WordColumn::Raw,
+        WordColumn::Address,
+        WordColumn::Arbiter,
+        WordColumn::Pileup,
+        WordColumn::Leading,
+        WordColumn::Trailing,
+        WordColumn::Tot,
+        WordColumn::Pixel,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            WordColumn::Index => "#",
+            WordColumn::Raw => "Raw",
+            WordColumn::Address => "Address",
+            WordColumn::Arbiter => "Arbiter",
+            WordColumn::Pileup => "Pileup",
+            WordColumn::Leading => "Leading",
+            WordColumn::Trailing => "Trailing",
+            WordColumn::Tot => "ToT",
+            WordColumn::Pixel => "Pixel",
+        }
+    }
+
+    fn sort_key(&self, idx: usize, dw: &DataWord) -> u64 {
+        match self {
+            WordColumn::Index => idx as u64,
+            WordColumn::Raw => dw.raw,
+            WordColumn::Address => dw.address as u64,
+            WordColumn::Arbiter => dw.address_arbiter as u64,
+            WordColumn::Pileup => dw.address_pileup as u64,
+            WordColumn::Leading => dw.get_start_time(),
+            WordColumn::Trailing => dw.get_start_time() + dw.get_duration(),
+            WordColumn::Tot => dw.get_duration(),
+            WordColumn::Pixel => {
+                let (x, y) = dw.pixel();
+                (x as u64) << 8 | y as u64
+            }
+        }
+    }
+
+    fn text(&self, idx: usize, dw: &DataWord) -> String {
+        match self {
+            WordColumn::Index => idx.to_string(),
+            WordColumn::Raw => format!("{:012x}", dw.raw),
+            WordColumn::Address => dw.address.to_string(),
+            WordColumn::Arbiter => format!("{:05b}", dw.address_arbiter),
+            WordColumn::Pileup => format!("{:05b}", dw.address_pileup),
+            WordColumn::Leading => format_time_precise(dw.get_start_time() as f64),
+            WordColumn::Trailing => {
+                format_time_precise((dw.get_start_time() + dw.get_duration()) as f64)
+            }
+            WordColumn::Tot => format_time_precise(dw.get_duration() as f64),
+            WordColumn::Pixel => format!("{:?}", dw.pixel()),
+        }
+    }
+}
+
+pub struct WordTableWindow<'a> {
+    main_app: &'a mut TDCpixApp,
+}
+
+impl<'a> WordTableWindow<'a> {
+    pub fn new(main_app: &'a mut TDCpixApp) -> Self {
+        WordTableWindow { main_app }
+    }
+
+    pub fn show(mut self, ctx: &egui::Context) {
+        let mut open = self.main_app.show_word_table;
+        egui::Window::new("Data words")
+            .open(&mut open)
+            .default_size(egui::vec2(640.0, 300.0))
+            .show(ctx, |ui| self.contents(ui));
+        self.main_app.show_word_table &= open;
+    }
+
+    fn contents(&mut self, ui: &mut egui::Ui) {
+        let app = &mut *self.main_app;
+        let chunk_idx = app.analysis_chunk_idx;
+        let Some(chunk) = app.chunks.get(chunk_idx) else {
+            ui.label("No data loaded.");
+            return;
+        };
+
+        let (sort_column, ascending) = app.word_table_sort;
+        let mut order: Vec<usize> = (0..chunk.data_words.len()).collect();
+        order.sort_by_key(|&i| sort_column.sort_key(i, &chunk.data_words[i]));
+        if !ascending {
+            order.reverse();
+        }
+
+        let selected_word = app
+            .selection
+            .and_then(|s| s.word)
+            .filter(|(c, _)| *c == chunk_idx)
+            .map(|(_, w)| w);
+
+        // Follow selections made on the grid or timeline
+        let mut table = TableBuilder::new(ui)
+            .striped(true)
+            .columns(Column::auto().at_least(40.0), WordColumn::ALL.len());
+        if app.word_table_scrolled != app.selection {
+            if let Some(row) = selected_word.and_then(|w| order.iter().position(|&i| i == w)) {
+                table = table.scroll_to_row(row, Some(egui::Align::Center));
+            }
+            app.word_table_scrolled = app.selection;
+        }
+
+        let mut clicked = None;
+        let mut sort_clicked = None;
+        table
+            .header(20.0, |mut header| {
+                for column in WordColumn::ALL {
+                    header.col(|ui| {
+                        let arrow = match (column == sort_column, ascending) {
+                            (false, _) => "",
+                            (true, true) => " ⏶",
+                            (true, false) => " ⏷",
+                        };
+                        let label = format!("{}{}", column.name(), arrow);
+                        if ui.button(egui::RichText::new(label).strong()).clicked() {
+                            sort_clicked = Some(column);
+                        }
+                    });
+                }
+            })
+            .body(|body| {
+                body.rows(18.0, order.len(), |row_idx, mut row| {
+                    let word_idx = order[row_idx];
+                    let dw = &chunk.data_words[word_idx];
+                    let selected = selected_word == Some(word_idx);
+                    for column in WordColumn::ALL {
+                        row.col(|ui| {
+                            let text = egui::RichText::new(column.text(word_idx, dw)).monospace();
+                            if ui.selectable_label(selected, text).clicked() {
+                                clicked = Some(word_idx);
+                            }
+                        });
+                    }
+                });
+            });
+
+        if let Some(column) = sort_clicked {
+            app.word_table_sort = if column == sort_column {
+                (column, !ascending)
+            } else {
+                (column, true)
+            };
+        }
+        if let Some(word_idx) = clicked {
+            let pixel = chunk.data_words[word_idx].pixel();
+            app.toggle_selection(pixel, Some((chunk_idx, word_idx)));
+            // The row is already in view
+            app.word_table_scrolled = app.selection;
+        }
+    }
+}